@@ -0,0 +1,210 @@
+use super::{
+    board::Board,
+    expectimax::{Difficulty, ExpectimaxAgent},
+};
+use instant::Duration;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// something a player can do on their turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// attack the second territory with the first
+    Attack(usize, usize),
+    EndTurn,
+}
+
+/// a computer opponent, it gets asked for one action at a time until it ends its turn
+pub trait Agent: Send + Sync {
+    /// picks the next action for [Board::current_player]
+    fn next_action(&mut self, board: &Board) -> Action;
+}
+
+/// who is playing a seat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Seat {
+    Human,
+    Bot(BotKind),
+}
+
+/// the built in bots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BotKind {
+    Random,
+    Greedy,
+    Cautious,
+    Expectimax(Difficulty),
+}
+
+impl BotKind {
+    pub const ALL: [BotKind; 6] = [
+        BotKind::Random,
+        BotKind::Greedy,
+        BotKind::Cautious,
+        BotKind::Expectimax(Difficulty::Easy),
+        BotKind::Expectimax(Difficulty::Normal),
+        BotKind::Expectimax(Difficulty::Hard),
+    ];
+
+    /// `time_limit` stops expectimax searches early so the game stays responsive, leave it
+    /// `None` where the same seed has to play out the same way on every machine
+    pub fn create(&self, seed: u64, time_limit: Option<Duration>) -> Box<dyn Agent> {
+        match self {
+            BotKind::Random => Box::new(RandomAgent::new(seed)),
+            BotKind::Greedy => Box::new(GreedyAgent),
+            BotKind::Cautious => Box::new(CautiousAgent),
+            BotKind::Expectimax(difficulty) => {
+                Box::new(ExpectimaxAgent::new(difficulty.budget(), time_limit))
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            BotKind::Expectimax(difficulty) => format!("Expectimax ({:?})", difficulty),
+            kind => format!("{:?}", kind),
+        }
+    }
+}
+
+/// picks a random move, or ends its turn with the same chance as any single move
+pub struct RandomAgent {
+    rng: ChaCha8Rng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn next_action(&mut self, board: &Board) -> Action {
+        let moves = board.all_moves();
+        let index = self.rng.gen_range(0..=moves.len());
+        match moves.get(index) {
+            Some((first, second)) => Action::Attack(*first, *second),
+            None => Action::EndTurn,
+        }
+    }
+}
+
+/// attacks whenever it has more dice than the defender, biggest advantage first
+pub struct GreedyAgent;
+
+impl Agent for GreedyAgent {
+    fn next_action(&mut self, board: &Board) -> Action {
+        board
+            .all_moves()
+            .into_iter()
+            .filter(|(first, second)| {
+                board.territories[*first].dice > board.territories[*second].dice
+            })
+            .max_by_key(|(first, second)| {
+                board.territories[*first].dice - board.territories[*second].dice
+            })
+            .map(|(first, second)| Action::Attack(first, second))
+            .unwrap_or(Action::EndTurn)
+    }
+}
+
+/// only attacks when it has more dice and the captured territory won't be left next to an enemy
+/// that can easily take it back, unless the attacker is already full
+pub struct CautiousAgent;
+
+impl CautiousAgent {
+    /// number of enemy territories next to `territory` that would have more dice than `dice`
+    fn threats(board: &Board, territory: usize, dice: u32, ignore: usize) -> usize {
+        let player = board.current_player();
+        board
+            .connections(territory)
+            .iter()
+            .filter(|neighbor| {
+                **neighbor != ignore
+                    && board.territories[**neighbor].owner != player
+                    && board.territories[**neighbor].dice > dice
+            })
+            .count()
+    }
+}
+
+impl Agent for CautiousAgent {
+    fn next_action(&mut self, board: &Board) -> Action {
+        let mut best = None;
+        for (first, second) in board.all_moves() {
+            let attacker = board.territories[first].dice;
+            let defender = board.territories[second].dice;
+            if attacker <= defender {
+                continue;
+            }
+
+            let threats = Self::threats(board, second, attacker - 1, first);
+            if threats > 0 && attacker < board.rules.max_dice {
+                continue;
+            }
+
+            // prefer safe attacks, then the biggest advantage
+            let score = (threats, defender as i32 - attacker as i32);
+            let better = match best {
+                Some((best_score, _)) => score < best_score,
+                None => true,
+            };
+            if better {
+                best = Some((score, Action::Attack(first, second)));
+            }
+        }
+        best.map(|(_, action)| action).unwrap_or(Action::EndTurn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardGenSettings;
+
+    #[test]
+    fn bots_only_make_legal_moves_and_end_their_turns() {
+        for kind in BotKind::ALL {
+            let mut board = Board::generate(&BoardGenSettings {
+                seed: Some(3),
+                ..Default::default()
+            })
+            .unwrap();
+            let mut agent = kind.create(3, None);
+            for _ in 0..10 {
+                // every attack leaves one die behind, so a turn can't go on forever
+                let mut actions = 0;
+                while let Action::Attack(first, second) = agent.next_action(&board) {
+                    assert_eq!(board.validate_attack(first, second), Ok(()), "{:?}", kind);
+                    board.try_attack(first, second).unwrap();
+                    actions += 1;
+                    assert!(actions < 1000, "{:?} never ended its turn", kind);
+                    if board.is_over() {
+                        break;
+                    }
+                }
+                if board.is_over() {
+                    break;
+                }
+                board.finish_turn();
+            }
+        }
+    }
+
+    #[test]
+    fn bots_without_a_time_limit_always_pick_the_same_action() {
+        let board = Board::generate(&BoardGenSettings {
+            seed: Some(3),
+            ..Default::default()
+        })
+        .unwrap();
+        for kind in BotKind::ALL {
+            let first = kind.create(3, None).next_action(&board);
+            let second = kind.create(3, None).next_action(&board);
+            assert_eq!(first, second, "{:?}", kind);
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! plays lots of bot vs bot games without rendering anything and prints the results
+//!
+//! example: `cargo run --release -p dicewars-core --bin dicewars-sim -- --games 1000 --bots greedy,cautious,hard`
+
+use dicewars_core::{
+    agent::{Action, Agent, BotKind},
+    board::{Board, BoardGenSettings, GenerateError, RuleSet},
+    expectimax::Difficulty,
+    generator::GeneratorSettings,
+};
+use serde_json::json;
+
+struct SimSettings {
+    games: usize,
+    players: usize,
+    board_size: usize,
+    territories: usize,
+    territory_size: usize,
+    average_dice: u32,
+    balanced: bool,
+    max_cluster: Option<u32>,
+    bots: Vec<BotKind>,
+    generator: GeneratorSettings,
+    /// games that go on longer than this are counted as draws
+    max_turns: usize,
+    seed: u64,
+    json: bool,
+}
+
+#[derive(Clone)]
+struct SeatStats {
+    bot: BotKind,
+    wins: usize,
+}
+
+/// totals for every seat a bot played, elo is rated per bot so seats with the same bot share it
+struct BotStats {
+    bot: BotKind,
+    seats: usize,
+    wins: usize,
+    elo: f64,
+}
+
+struct Results {
+    seats: Vec<SeatStats>,
+    bots: Vec<BotStats>,
+    draws: usize,
+    total_turns: usize,
+    first_player_wins: usize,
+    total_fairness: f32,
+}
+
+const USAGE: &str = "usage: dicewars-sim [--games N] [--players N] [--board-size N] [--max-turns N]
+                    [--territories N] [--territory-size N] [--dice N] [--max-cluster N]
+                    [--seed N] [--bots random,greedy,cautious,easy,normal,hard]
+                    [--generator grow|voronoi|islands|symmetric] [--unbalanced] [--json]
+
+bots are given to the seats in order and repeat if there are more players than bots";
+
+fn parse_bot(name: &str) -> Result<BotKind, String> {
+    match name {
+        "random" => Ok(BotKind::Random),
+        "greedy" => Ok(BotKind::Greedy),
+        "cautious" => Ok(BotKind::Cautious),
+        "easy" => Ok(BotKind::Expectimax(Difficulty::Easy)),
+        "normal" => Ok(BotKind::Expectimax(Difficulty::Normal)),
+        "hard" => Ok(BotKind::Expectimax(Difficulty::Hard)),
+        _ => Err(format!("unknown bot '{}'", name)),
+    }
+}
+
+fn parse_generator(name: &str) -> Result<GeneratorSettings, String> {
+    GeneratorSettings::ALL
+        .into_iter()
+        .find(|generator| generator.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown generator '{}'", name))
+}
+
+fn parse_args() -> Result<SimSettings, String> {
+    let mut settings = SimSettings {
+        games: 1000,
+        players: 4,
+        board_size: 20,
+        territories: 24,
+        territory_size: 10,
+        average_dice: 3,
+        balanced: true,
+        max_cluster: None,
+        bots: vec![BotKind::Greedy, BotKind::Cautious],
+        generator: GeneratorSettings::default(),
+        max_turns: 2000,
+        seed: 0,
+        json: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--json" {
+            settings.json = true;
+            continue;
+        }
+        if arg == "--unbalanced" {
+            settings.balanced = false;
+            continue;
+        }
+        if arg == "--help" || arg == "-h" {
+            return Err(USAGE.to_string());
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", arg))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("{} expects a number", arg))
+        };
+        match arg.as_str() {
+            "--games" => settings.games = number()?,
+            "--players" => settings.players = number()?,
+            "--board-size" => settings.board_size = number()?,
+            "--max-turns" => settings.max_turns = number()?,
+            "--territories" => settings.territories = number()?,
+            "--territory-size" => settings.territory_size = number()?,
+            "--dice" => settings.average_dice = number()? as u32,
+            "--max-cluster" => settings.max_cluster = Some(number()? as u32),
+            "--seed" => settings.seed = number()? as u64,
+            "--bots" => {
+                settings.bots = value
+                    .split(',')
+                    .map(parse_bot)
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            "--generator" => settings.generator = parse_generator(&value)?,
+            _ => return Err(format!("unknown argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    if settings.players < 2 || settings.players > 8 {
+        return Err("--players must be between 2 and 8".to_string());
+    }
+    if settings.bots.is_empty() {
+        return Err("--bots needs at least one bot".to_string());
+    }
+    settings
+        .board_settings(settings.seed)
+        .validate()
+        .map_err(|error| error.to_string())?;
+    Ok(settings)
+}
+
+impl SimSettings {
+    fn board_settings(&self, seed: u64) -> BoardGenSettings {
+        BoardGenSettings {
+            player_count: self.players,
+            board_size: self.board_size,
+            num_territories: self.territories,
+            territory_size: self.territory_size,
+            average_dice: self.average_dice,
+            balanced: self.balanced,
+            max_starting_cluster: self.max_cluster,
+            rules: RuleSet::default(),
+            seed: Some(seed),
+            seats: Vec::new(),
+            generator: self.generator,
+        }
+    }
+}
+
+/// plays one game and returns who went first, the winner, or `None` if it hit the turn limit,
+/// how many turns it took and how fair the starting board was
+fn play_game(
+    settings: &SimSettings,
+    seed: u64,
+) -> Result<(usize, Option<usize>, usize, f32), GenerateError> {
+    let mut board = Board::generate(&settings.board_settings(seed))?;
+    let fairness = board.fairness();
+    // no time limit, so a seed plays out the same however fast the machine is
+    let mut agents = (0..settings.players)
+        .map(|seat| settings.bots[seat % settings.bots.len()].create(seed ^ seat as u64, None))
+        .collect::<Vec<Box<dyn Agent>>>();
+
+    let first_player = board.player_order[0];
+    let mut turns = 0;
+    while !board.is_over() && turns < settings.max_turns {
+        let player = board.current_player();
+        match agents[player].next_action(&board) {
+            Action::Attack(first, second) => {
+                if board.try_attack(first, second).is_err() {
+                    board.finish_turn();
+                    turns += 1;
+                }
+            }
+            Action::EndTurn => {
+                board.finish_turn();
+                turns += 1;
+            }
+        }
+    }
+
+    let winner = board.winner();
+    Ok((first_player, winner, turns, fairness))
+}
+
+/// rates the winner against every other seat, or every seat against each other for a draw.
+/// `seat_bots` is the index in `bots` of each seat's bot, seats with the same bot are skipped
+fn update_elo(bots: &mut [BotStats], seat_bots: &[usize], winner: Option<usize>) {
+    const K: f64 = 16.0;
+    // ratings from before the game so the order seats are rated in doesn't matter
+    let ratings = bots.iter().map(|stats| stats.elo).collect::<Vec<_>>();
+    for first in 0..seat_bots.len() {
+        for second in first + 1..seat_bots.len() {
+            let (first_bot, second_bot) = (seat_bots[first], seat_bots[second]);
+            if first_bot == second_bot {
+                continue;
+            }
+            let score = match winner {
+                Some(winner) if winner == first => 1.0,
+                Some(winner) if winner == second => 0.0,
+                // two losers tell us nothing about each other
+                Some(_) => continue,
+                None => 0.5,
+            };
+            let expected =
+                1.0 / (1.0 + 10f64.powf((ratings[second_bot] - ratings[first_bot]) / 400.0));
+            let change = K * (score - expected);
+            bots[first_bot].elo += change;
+            bots[second_bot].elo -= change;
+        }
+    }
+}
+
+fn simulate(settings: &SimSettings) -> Result<Results, String> {
+    let seats = (0..settings.players)
+        .map(|seat| settings.bots[seat % settings.bots.len()])
+        .collect::<Vec<_>>();
+    let mut bots = Vec::<BotStats>::new();
+    let mut seat_bots = Vec::new();
+    for bot in seats.iter() {
+        let index = match bots.iter().position(|stats| stats.bot == *bot) {
+            Some(index) => index,
+            None => {
+                bots.push(BotStats {
+                    bot: *bot,
+                    seats: 0,
+                    wins: 0,
+                    elo: 1000.0,
+                });
+                bots.len() - 1
+            }
+        };
+        bots[index].seats += 1;
+        seat_bots.push(index);
+    }
+
+    let mut results = Results {
+        seats: seats
+            .into_iter()
+            .map(|bot| SeatStats { bot, wins: 0 })
+            .collect(),
+        bots,
+        draws: 0,
+        total_turns: 0,
+        first_player_wins: 0,
+        total_fairness: 0.0,
+    };
+
+    for game in 0..settings.games {
+        let seed = settings.seed.wrapping_add(game as u64);
+        let (first_player, winner, turns, fairness) = play_game(settings, seed)
+            .map_err(|error| format!("couldn't generate a board with seed {}: {}", seed, error))?;
+        results.total_turns += turns;
+        results.total_fairness += fairness;
+        match winner {
+            Some(winner) => {
+                results.seats[winner].wins += 1;
+                results.bots[seat_bots[winner]].wins += 1;
+                if winner == first_player {
+                    results.first_player_wins += 1;
+                }
+            }
+            None => results.draws += 1,
+        }
+        update_elo(&mut results.bots, &seat_bots, winner);
+    }
+    Ok(results)
+}
+
+fn print_csv(settings: &SimSettings, results: &Results) {
+    println!("seat,bot,wins,win_rate");
+    for (seat, stats) in results.seats.iter().enumerate() {
+        println!(
+            "{},{},{},{:.4}",
+            seat,
+            stats.bot.name(),
+            stats.wins,
+            stats.wins as f64 / settings.games as f64
+        );
+    }
+    println!();
+    println!("bot,seats,wins,win_rate,elo");
+    for stats in results.bots.iter() {
+        println!(
+            "{},{},{},{:.4},{:.1}",
+            stats.bot.name(),
+            stats.seats,
+            stats.wins,
+            stats.wins as f64 / (settings.games * stats.seats) as f64,
+            stats.elo
+        );
+    }
+    println!();
+    println!("stat,value");
+    println!("games,{}", settings.games);
+    println!("draws,{}", results.draws);
+    println!(
+        "average_turns,{:.2}",
+        results.total_turns as f64 / settings.games as f64
+    );
+    println!(
+        "first_player_win_rate,{:.4}",
+        results.first_player_wins as f64 / settings.games as f64
+    );
+    println!(
+        "average_fairness,{:.4}",
+        results.total_fairness / settings.games as f32
+    );
+}
+
+fn print_json(settings: &SimSettings, results: &Results) {
+    let games = settings.games as f64;
+    let seats = results
+        .seats
+        .iter()
+        .enumerate()
+        .map(|(seat, stats)| {
+            json!({
+                "seat": seat,
+                "bot": stats.bot.name(),
+                "wins": stats.wins,
+                "win_rate": stats.wins as f64 / games,
+            })
+        })
+        .collect::<Vec<_>>();
+    let bots = results
+        .bots
+        .iter()
+        .map(|stats| {
+            json!({
+                "bot": stats.bot.name(),
+                "seats": stats.seats,
+                "wins": stats.wins,
+                "win_rate": stats.wins as f64 / (games * stats.seats as f64),
+                "elo": stats.elo,
+            })
+        })
+        .collect::<Vec<_>>();
+    let output = json!({
+        "games": settings.games,
+        "players": settings.players,
+        "board_size": settings.board_size,
+        "draws": results.draws,
+        "average_turns": results.total_turns as f64 / games,
+        "first_player_win_rate": results.first_player_wins as f64 / games,
+        "average_fairness": results.total_fairness / settings.games as f32,
+        "seats": seats,
+        "bots": bots,
+    });
+    println!("{}", output);
+}
+
+fn main() {
+    let (settings, results) = match parse_args()
+        .and_then(|settings| simulate(&settings).map(|results| (settings, results)))
+    {
+        Ok(result) => result,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    if settings.json {
+        print_json(&settings, &results);
+    } else {
+        print_csv(&settings, &results);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SimSettings {
+        SimSettings {
+            games: 5,
+            players: 3,
+            board_size: 20,
+            territories: 24,
+            territory_size: 10,
+            average_dice: 3,
+            balanced: true,
+            max_cluster: None,
+            bots: vec![BotKind::Greedy, BotKind::Cautious],
+            generator: GeneratorSettings::default(),
+            max_turns: 2000,
+            seed: 7,
+            json: false,
+        }
+    }
+
+    #[test]
+    fn every_game_is_a_win_or_a_draw() {
+        let results = simulate(&settings()).unwrap();
+        let wins = results.seats.iter().map(|seat| seat.wins).sum::<usize>();
+        assert_eq!(wins + results.draws, 5);
+        assert_eq!(results.bots.iter().map(|bot| bot.wins).sum::<usize>(), wins);
+        assert!(results.first_player_wins <= wins);
+        assert!(results.total_fairness > 0.0 && results.total_fairness <= 5.0);
+    }
+
+    #[test]
+    fn boards_that_cant_be_generated_are_an_error() {
+        let settings = SimSettings {
+            board_size: 7,
+            ..settings()
+        };
+        assert!(simulate(&settings).is_err());
+    }
+
+    #[test]
+    fn generators_are_parsed_by_name() {
+        for generator in GeneratorSettings::ALL {
+            let name = generator.name().to_lowercase();
+            assert_eq!(parse_generator(&name), Ok(generator));
+        }
+        assert!(parse_generator("hills").is_err());
+    }
+
+    /// greedy plays seat 0 and cautious seats 1 and 2
+    fn bots() -> Vec<BotStats> {
+        [(BotKind::Greedy, 1), (BotKind::Cautious, 2)]
+            .into_iter()
+            .map(|(bot, seats)| BotStats {
+                bot,
+                seats,
+                wins: 0,
+                elo: 1000.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn elo_moves_from_the_losers_to_the_winner() {
+        let mut bots = bots();
+        update_elo(&mut bots, &[0, 1, 1], Some(0));
+        assert!(bots[0].elo > 1000.0 && bots[1].elo < 1000.0);
+        assert!((bots[0].elo + bots[1].elo - 2000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn draws_between_even_bots_change_nothing() {
+        let mut bots = bots();
+        update_elo(&mut bots, &[0, 1, 1], None);
+        assert!(bots.iter().all(|stats| (stats.elo - 1000.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn seats_with_the_same_bot_dont_rate_each_other() {
+        let mut bots = bots();
+        update_elo(&mut bots, &[1, 1], Some(0));
+        assert_eq!(bots[1].elo, 1000.0);
+    }
+}
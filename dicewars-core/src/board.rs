@@ -0,0 +1,1363 @@
+use super::{
+    agent::{BotKind, Seat},
+    generator::GeneratorSettings,
+    hex::Hex,
+    hex_map::HexMap,
+    scenario::Scenario,
+};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// maximum number of bonus dice a player can have stored up, same as the original game
+pub const MAX_STOCKPILE: u32 = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub rules: RuleSet,
+    pub turn: usize,
+    pub player_order: Vec<usize>,
+    pub territories: Vec<Territory>,
+    /// bonus dice that didn't fit on any territory, indexed by player
+    pub stockpiles: Vec<u32>,
+    /// seed the board was generated from, generating with the same seed and settings gives the
+    /// same game
+    pub seed: u64,
+    rng: ChaCha8Rng,
+    /// never changes during a game, so clones of the board share it
+    pub map: Arc<HexMap>,
+    /// ways to win besides being the last player left, checked at the end of every turn
+    #[serde(default)]
+    pub win_conditions: Vec<WinCondition>,
+    /// number of times every player has had a turn
+    #[serde(default)]
+    pub round: u32,
+    #[serde(default)]
+    outcome: GameOutcome,
+}
+
+/// whether the game has been won yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    #[default]
+    InProgress,
+    /// `player` met `goal`, [Goal::Eliminate] when they were the last player left
+    Won { player: usize, goal: Goal },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinCondition {
+    /// player this applies to, `None` for every player
+    pub player: Option<usize>,
+    pub goal: Goal,
+}
+
+/// what a player has to do to win, the last player left always wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    /// knock every other player out
+    Eliminate,
+    /// own at least this many territories at the end of a turn
+    HoldTerritories(u32),
+    /// still be in the game at the end of this many of your own turns
+    Survive(u32),
+}
+
+impl Goal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Goal::Eliminate => "Eliminate",
+            Goal::HoldTerritories(_) => "Hold territories",
+            Goal::Survive(_) => "Survive turns",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Territory {
+    pub owner: usize,
+    pub dice: u32,
+    connections: Vec<usize>,
+}
+
+/// maximum number of players a board can be generated for
+pub const MAX_PLAYERS: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardGenSettings {
+    pub player_count: usize,
+    /// radius of the board in hexes
+    pub board_size: usize,
+    pub num_territories: usize,
+    /// number of hexes each territory grows by before it gets smoothed, generators that don't
+    /// grow territories use it to space them out
+    pub territory_size: usize,
+    /// dice each territory starts with on average, including the one every territory gets
+    pub average_dice: u32,
+    /// give every player the same number of territories, leaving out the ones that don't split
+    /// evenly. otherwise everyone gets one and the rest go to random players. players get the
+    /// same dice either way
+    pub balanced: bool,
+    /// largest group of connected territories a player can start with, `None` for no limit.
+    /// symmetric maps that give each player a copy ignore this so they stay symmetric
+    pub max_starting_cluster: Option<u32>,
+    pub rules: RuleSet,
+    /// random seed if `None`
+    pub seed: Option<u64>,
+    /// who plays each player, indexed by player
+    pub seats: Vec<Seat>,
+    pub generator: GeneratorSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    /// number of faces on each die
+    pub die_faces: u32,
+    /// maximum number of dice a territory can hold
+    pub max_dice: u32,
+    pub tie_break: TieBreak,
+}
+
+/// who wins an attack when both sides roll the same total
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TieBreak {
+    Defender,
+    Attacker,
+    Random,
+}
+
+impl Default for Board {
+    /// empty board with no players
+    fn default() -> Self {
+        Self {
+            rules: RuleSet::default(),
+            turn: 0,
+            player_order: Vec::new(),
+            territories: Vec::new(),
+            stockpiles: Vec::new(),
+            seed: 0,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            map: Arc::default(),
+            win_conditions: Vec::new(),
+            round: 0,
+            outcome: GameOutcome::InProgress,
+        }
+    }
+}
+
+/// everything that happened during a single attack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttackOutcome {
+    /// attacking territory
+    pub attacker: usize,
+    /// defending territory
+    pub defender: usize,
+    pub attacker_rolls: Vec<u32>,
+    pub defender_rolls: Vec<u32>,
+    pub attacker_total: u32,
+    pub defender_total: u32,
+    pub win: bool,
+    /// owner of the defending territory after the attack
+    pub defender_owner: usize,
+    /// dice left on the attacking territory after the attack
+    pub attacker_dice: u32,
+    /// dice on the defending territory after the attack
+    pub defender_dice: u32,
+}
+
+/// reasons an attack can be rejected by [Board::try_attack]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NotYourTerritory,
+    NotEnoughDice,
+    NotAdjacent,
+    OwnTerritory,
+    OutOfRange,
+    GameOver,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            MoveError::NotYourTerritory => {
+                "the attacking territory isn't owned by the current player"
+            }
+            MoveError::NotEnoughDice => "the attacking territory needs more than one die",
+            MoveError::NotAdjacent => "the territories aren't next to each other",
+            MoveError::OwnTerritory => "can't attack your own territory",
+            MoveError::OutOfRange => "territory index out of range",
+            MoveError::GameOver => "the game is over",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// reasons [Board::generate] can't make a board from the settings or a [Scenario] can't be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerateError {
+    PlayerCount(usize),
+    /// every player needs at least one territory
+    NotEnoughTerritories {
+        territories: usize,
+        players: usize,
+    },
+    /// the territories can't fit on a board this size even if they were packed perfectly
+    BoardTooSmall {
+        needed: usize,
+        available: usize,
+    },
+    /// average dice has to be between 1 and the rule set's max dice
+    Dice {
+        average: u32,
+        max: u32,
+    },
+    /// symmetric maps need the same number of territories in every copy
+    NotSymmetric {
+        territories: usize,
+        folds: usize,
+    },
+    /// the generator gave up before placing every territory
+    CouldNotFit {
+        requested: usize,
+        generated: usize,
+    },
+    /// the generator kept making territories that can't all be reached from each other
+    Disconnected,
+    /// owners couldn't be shuffled so every starting cluster is within the limit
+    ClusterLimit(u32),
+    /// a scenario territory with no hexes
+    EmptyTerritory(usize),
+    /// a hex that's in more than one scenario territory
+    Overlap(Hex),
+    /// a scenario territory owned by a player that isn't in the game
+    Owner {
+        territory: usize,
+        owner: usize,
+    },
+    /// a scenario territory with no dice or more than the rule set allows
+    TerritoryDice {
+        territory: usize,
+        dice: u32,
+    },
+    /// a scenario player that doesn't own any territories
+    NoTerritories(usize),
+    /// a scenario has to give a seat to every player or leave them all to the game
+    Seats {
+        seats: usize,
+        players: usize,
+    },
+    /// a win condition for a player that isn't in the game
+    ConditionPlayer(usize),
+    /// a win condition that's met before anyone has played
+    Goal(Goal),
+    /// a rule set with dice that can't be rolled or territories that can't hold any
+    Rules {
+        die_faces: u32,
+        max_dice: u32,
+    },
+}
+
+impl std::fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerateError::PlayerCount(players) => write!(
+                f,
+                "{} players isn't allowed, there can be 2 to {}",
+                players, MAX_PLAYERS
+            ),
+            GenerateError::NotEnoughTerritories {
+                territories,
+                players,
+            } => write!(
+                f,
+                "{} territories isn't enough for {} players",
+                territories, players
+            ),
+            GenerateError::BoardTooSmall { needed, available } => write!(
+                f,
+                "the territories need at least {} hexes but the board only has {}",
+                needed, available
+            ),
+            GenerateError::Dice { average, max } => write!(
+                f,
+                "average dice is {} but has to be between 1 and the max dice of {}",
+                average, max
+            ),
+            GenerateError::NotSymmetric { territories, folds } => write!(
+                f,
+                "{} territories can't be split evenly into {} copies",
+                territories, folds
+            ),
+            GenerateError::CouldNotFit {
+                requested,
+                generated,
+            } => write!(
+                f,
+                "only {} of {} territories fit, try a bigger board or smaller territories",
+                generated, requested
+            ),
+            GenerateError::Disconnected => {
+                write!(f, "the generator couldn't make a connected board")
+            }
+            GenerateError::ClusterLimit(limit) => write!(
+                f,
+                "couldn't keep every player's starting cluster to {} territories",
+                limit
+            ),
+            GenerateError::EmptyTerritory(territory) => {
+                write!(f, "territory {} doesn't have any hexes", territory)
+            }
+            GenerateError::Overlap(hex) => write!(
+                f,
+                "hex ({}, {}, {}) is in more than one territory",
+                hex.q, hex.r, hex.s
+            ),
+            GenerateError::Owner { territory, owner } => write!(
+                f,
+                "territory {} is owned by player {} who isn't in the game",
+                territory, owner
+            ),
+            GenerateError::TerritoryDice { territory, dice } => write!(
+                f,
+                "territory {} has {} dice which the rules don't allow",
+                territory, dice
+            ),
+            GenerateError::NoTerritories(player) => {
+                write!(f, "player {} doesn't own any territories", player)
+            }
+            GenerateError::Seats { seats, players } => write!(
+                f,
+                "there are {} seats for {} players, leave them empty to use the game's seats",
+                seats, players
+            ),
+            GenerateError::ConditionPlayer(player) => write!(
+                f,
+                "there's a win condition for player {} who isn't in the game",
+                player
+            ),
+            GenerateError::Goal(goal) => {
+                write!(f, "the {} goal needs a number above 0", goal.name())
+            }
+            GenerateError::Rules {
+                die_faces,
+                max_dice,
+            } => write!(
+                f,
+                "dice need at least 1 face and territories room for 1 die, not {} and {}",
+                die_faces, max_dice
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+impl RuleSet {
+    /// the rules from the original game
+    pub const CLASSIC: RuleSet = RuleSet {
+        die_faces: 6,
+        max_dice: 8,
+        tie_break: TieBreak::Defender,
+    };
+
+    /// dice need at least one face to be rolled and territories need room for at least one die
+    pub fn is_playable(&self) -> bool {
+        self.die_faces > 0 && self.max_dice > 0
+    }
+}
+
+impl Default for BoardGenSettings {
+    /// one human against greedy bots
+    fn default() -> Self {
+        Self {
+            player_count: 3,
+            board_size: 20,
+            num_territories: 24,
+            territory_size: 10,
+            average_dice: 3,
+            balanced: true,
+            max_starting_cluster: None,
+            rules: RuleSet::default(),
+            seed: None,
+            seats: std::iter::once(Seat::Human)
+                .chain(std::iter::repeat_n(
+                    Seat::Bot(BotKind::Greedy),
+                    MAX_PLAYERS - 1,
+                ))
+                .collect(),
+            generator: GeneratorSettings::default(),
+        }
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+impl BoardGenSettings {
+    /// catches settings that can never make a board, [Board::generate] can still fail if the
+    /// generator is unlucky
+    pub fn validate(&self) -> Result<(), GenerateError> {
+        if self.player_count < 2 || self.player_count > MAX_PLAYERS {
+            return Err(GenerateError::PlayerCount(self.player_count));
+        }
+        if self.num_territories < self.player_count {
+            return Err(GenerateError::NotEnoughTerritories {
+                territories: self.num_territories,
+                players: self.player_count,
+            });
+        }
+        let needed = self.num_territories * (self.territory_size + 1);
+        let available = Hex::disc(self.board_size as i32).len();
+        if needed > available {
+            return Err(GenerateError::BoardTooSmall { needed, available });
+        }
+        if !self.rules.is_playable() {
+            return Err(GenerateError::Rules {
+                die_faces: self.rules.die_faces,
+                max_dice: self.rules.max_dice,
+            });
+        }
+        if self.average_dice == 0 || self.average_dice > self.rules.max_dice {
+            return Err(GenerateError::Dice {
+                average: self.average_dice,
+                max: self.rules.max_dice,
+            });
+        }
+        self.generator.generator().validate(self)
+    }
+}
+
+impl Board {
+    /// number of times the generator is run before giving up on fitting every territory
+    const GENERATE_ATTEMPTS: usize = 10;
+    /// number of owner swaps tried to get every starting cluster under the limit
+    const CLUSTER_SWAPS: usize = 1000;
+
+    pub fn generate(board_gen_settings: &BoardGenSettings) -> Result<Self, GenerateError> {
+        board_gen_settings.validate()?;
+
+        let seed = board_gen_settings.seed.unwrap_or_else(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let generator = board_gen_settings.generator.generator();
+        let mut map = HexMap::default();
+        let mut connections = Vec::new();
+        for _ in 0..Self::GENERATE_ATTEMPTS {
+            map = HexMap::new(generator.generate(board_gen_settings, &mut rng));
+            connections = map.connections();
+            if map.territory_tiles.len() >= board_gen_settings.num_territories
+                && is_connected(&connections)
+            {
+                break;
+            }
+        }
+        if map.territory_tiles.len() < board_gen_settings.num_territories {
+            return Err(GenerateError::CouldNotFit {
+                requested: board_gen_settings.num_territories,
+                generated: map.territory_tiles.len(),
+            });
+        }
+        if !is_connected(&connections) {
+            return Err(GenerateError::Disconnected);
+        }
+
+        // leave out territories that can't be shared evenly, picking ones the rest of the board
+        // stays connected without. there's always one since a connected board has a spanning
+        // tree and removing one of its leaves keeps the rest connected
+        if board_gen_settings.balanced {
+            let mut territory_tiles = map.territory_tiles.clone();
+            for _ in 0..territory_tiles.len() % board_gen_settings.player_count {
+                let mut candidates = (0..territory_tiles.len()).collect::<Vec<_>>();
+                candidates.shuffle(&mut rng);
+                for candidate in candidates {
+                    let mut trimmed = territory_tiles.clone();
+                    trimmed.remove(candidate);
+                    if is_connected(&HexMap::new(trimmed.clone()).connections()) {
+                        territory_tiles = trimmed;
+                        break;
+                    }
+                }
+            }
+            map = HexMap::new(territory_tiles);
+            connections = map.connections();
+        }
+
+        let mut territories = connections
+            .into_iter()
+            .map(|connections| Territory {
+                owner: 0,
+                dice: 1,
+                connections,
+            })
+            .collect::<Vec<_>>();
+
+        // distribue territoryes between players
+        let symmetric = generator.symmetry() == board_gen_settings.player_count;
+        let mut territorys_left = (0..territories.len()).collect::<Vec<_>>();
+        let mut player_territorys = vec![Vec::new(); board_gen_settings.player_count];
+        'outer2: while !territorys_left.is_empty() {
+            // give each player the same copy of every territory on symmetric maps
+            if symmetric {
+                for (territory, owner) in territorys_left
+                    .drain(..)
+                    .zip((0..).map(|i| i % generator.symmetry()))
+                {
+                    territories[territory].owner = owner;
+                    player_territorys[owner].push(territory);
+                }
+                break;
+            }
+
+            for i in 0..board_gen_settings.player_count {
+                let index = rng.gen_range(0..territorys_left.len());
+                let territory = territorys_left.remove(index);
+                // everyone gets a territory in the first round even on unbalanced boards
+                let owner = if board_gen_settings.balanced || player_territorys[i].is_empty() {
+                    i
+                } else {
+                    rng.gen_range(0..board_gen_settings.player_count)
+                };
+                territories[territory].owner = owner;
+                player_territorys[owner].push(territory);
+
+                if territorys_left.is_empty() {
+                    break 'outer2;
+                }
+            }
+        }
+
+        // swap owners until nobody starts with too big a cluster
+        if let (Some(limit), false) = (board_gen_settings.max_starting_cluster, symmetric) {
+            let excess = |territories: &[Territory]| {
+                (0..board_gen_settings.player_count)
+                    .map(|player| largest_region(territories, player).saturating_sub(limit))
+                    .sum::<u32>()
+            };
+            let mut current = excess(&territories);
+            for _ in 0..Self::CLUSTER_SWAPS {
+                if current == 0 {
+                    break;
+                }
+                let first = rng.gen_range(0..territories.len());
+                let second = rng.gen_range(0..territories.len());
+                let (first_owner, second_owner) =
+                    (territories[first].owner, territories[second].owner);
+                territories[first].owner = second_owner;
+                territories[second].owner = first_owner;
+                let new = excess(&territories);
+                if new <= current {
+                    current = new;
+                } else {
+                    territories[first].owner = first_owner;
+                    territories[second].owner = second_owner;
+                }
+            }
+            if current > 0 {
+                return Err(GenerateError::ClusterLimit(limit));
+            }
+            for (player, owned) in player_territorys.iter_mut().enumerate() {
+                *owned = (0..territories.len())
+                    .filter(|territory| territories[*territory].owner == player)
+                    .collect();
+            }
+        }
+
+        // distribute dice to territories
+        let total_dice = (board_gen_settings.average_dice as usize - 1) * territories.len();
+        let dice_per_player = total_dice / board_gen_settings.player_count;
+
+        for i in 0..board_gen_settings.player_count {
+            // copy the first player's dice onto the other copies on symmetric maps
+            if symmetric && i > 0 {
+                for (index, territory) in player_territorys[i].iter().enumerate() {
+                    territories[*territory].dice = territories[player_territorys[0][index]].dice;
+                }
+                continue;
+            }
+
+            let max_dice = board_gen_settings.rules.max_dice;
+            let mut dice_left = dice_per_player;
+            // players with one territory less than the others might not fit all their dice
+            while dice_left > 0
+                && player_territorys[i]
+                    .iter()
+                    .any(|territory| territories[*territory].dice < max_dice)
+            {
+                let index = rng.gen_range(0..player_territorys[i].len());
+                let territory = player_territorys[i][index];
+                let territory = territories.get_mut(territory).unwrap();
+                if territory.dice < max_dice {
+                    territory.dice += 1;
+                    dice_left -= 1;
+                }
+            }
+        }
+
+        // random player order
+        let mut player_order = (0..board_gen_settings.player_count).collect::<Vec<_>>();
+        player_order.shuffle(&mut rng);
+
+        Ok(Self {
+            rules: board_gen_settings.rules,
+            turn: 0,
+            player_order,
+            territories,
+            stockpiles: vec![0; board_gen_settings.player_count],
+            seed,
+            rng,
+            map: Arc::new(map),
+            win_conditions: Vec::new(),
+            round: 0,
+            outcome: GameOutcome::InProgress,
+        })
+    }
+
+    /// attack `second` with `first`, returns an error and leaves the board untouched if the move
+    /// isn't allowed
+    pub fn try_attack(&mut self, first: usize, second: usize) -> Result<AttackOutcome, MoveError> {
+        self.validate_attack(first, second)?;
+        let outcome = self.make_move(first, second);
+        // taking a player's last territory knocks them out straight away
+        if outcome.win {
+            self.remove_eliminated();
+        }
+        Ok(outcome)
+    }
+
+    /// checks if the current player is allowed to attack `second` with `first`
+    pub fn validate_attack(&self, first: usize, second: usize) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        if first >= self.territories.len() || second >= self.territories.len() {
+            return Err(MoveError::OutOfRange);
+        }
+        if self.territories[first].owner != self.current_player() {
+            return Err(MoveError::NotYourTerritory);
+        }
+        if self.territories[second].owner == self.current_player() {
+            return Err(MoveError::OwnTerritory);
+        }
+        if !self.territories[first].connections.contains(&second) {
+            return Err(MoveError::NotAdjacent);
+        }
+        if self.territories[first].dice <= 1 {
+            return Err(MoveError::NotEnoughDice);
+        }
+        Ok(())
+    }
+
+    /// note: this funciton assumes that the move is valid, [Board::validate_attack] should be used to
+    /// check if the move is valid first.
+    fn make_move(&mut self, first: usize, second: usize) -> AttackOutcome {
+        // roll the dice!
+        let attacker_rolls = (0..self.territories[first].dice)
+            .map(|_| self.rng.gen_range(1..=self.rules.die_faces))
+            .collect::<Vec<_>>();
+        let defender_rolls = (0..self.territories[second].dice)
+            .map(|_| self.rng.gen_range(1..=self.rules.die_faces))
+            .collect::<Vec<_>>();
+        let attacker_total = attacker_rolls.iter().sum::<u32>();
+        let defender_total = defender_rolls.iter().sum::<u32>();
+
+        let win = match self.rules.tie_break {
+            _ if attacker_total != defender_total => attacker_total > defender_total,
+            TieBreak::Defender => false,
+            TieBreak::Attacker => true,
+            TieBreak::Random => self.rng.gen(),
+        };
+
+        self.resolve_attack(first, second, win);
+
+        AttackOutcome {
+            attacker: first,
+            defender: second,
+            attacker_rolls,
+            defender_rolls,
+            attacker_total,
+            defender_total,
+            win,
+            defender_owner: self.territories[second].owner,
+            attacker_dice: self.territories[first].dice,
+            defender_dice: self.territories[second].dice,
+        }
+    }
+
+    /// moves the dice and ownership for an attack that has already been decided, without rolling
+    pub fn resolve_attack(&mut self, first: usize, second: usize, win: bool) {
+        if win {
+            self.territories[second].owner = self.territories[first].owner;
+            self.territories[second].dice = self.territories[first].dice - 1;
+        }
+        self.territories[first].dice = 1;
+    }
+
+    /// every attack the current player can make as (from, to)
+    pub fn all_moves(&self) -> Vec<(usize, usize)> {
+        let mut moves = Vec::new();
+        if !self.is_over() {
+            for first in 0..self.territories.len() {
+                for second in self.available_moves(first) {
+                    moves.push((first, second));
+                }
+            }
+        }
+        moves
+    }
+
+    pub fn available_moves(&self, first: usize) -> Vec<usize> {
+        let mut moves = Vec::new();
+        if self.territories[first].owner == self.player_order[self.turn]
+            && self.territories[first].dice > 1
+        {
+            for second in self.territories[first].connections.iter() {
+                if self.territories[first].owner != self.territories[*second].owner {
+                    moves.push(*second);
+                }
+            }
+        }
+        moves
+    }
+
+    /// number of territories owned by each player
+    pub fn count_territories(&self) -> Vec<u32> {
+        let mut income = vec![0; MAX_PLAYERS];
+        for territory in self.territories.iter() {
+            income[territory.owner] += 1;
+        }
+        income
+    }
+
+    pub fn finish_turn(&mut self) {
+        let scores = self.scores().1;
+        let (player, score) = scores[self.turn];
+
+        let mut player_teritories = Vec::new();
+        for territory in self.territories.iter_mut() {
+            if territory.owner == player && territory.dice < self.rules.max_dice {
+                player_teritories.push(territory);
+            }
+        }
+
+        // spend stockpiled dice first, then the new bonus
+        let mut dice_left = self.stockpiles[player] + score;
+        while dice_left > 0 && !player_teritories.is_empty() {
+            let index = self.rng.gen_range(0..player_teritories.len());
+            let territory = &mut player_teritories[index];
+            territory.dice += 1;
+            dice_left -= 1;
+            if territory.dice >= self.rules.max_dice {
+                player_teritories.remove(index);
+            }
+        }
+
+        // add extra dice to player's bonus
+        self.stockpiles[player] = dice_left.min(MAX_STOCKPILE);
+
+        self.remove_eliminated();
+        self.check_win(player);
+
+        self.turn += 1;
+        if self.turn >= self.player_order.len() {
+            self.turn = 0;
+            self.round += 1;
+        }
+    }
+
+    /// takes players without territories out of the turn order, the last player left wins
+    fn remove_eliminated(&mut self) {
+        let territory_counts = self.count_territories();
+        for (i, count) in territory_counts.iter().enumerate() {
+            if *count == 0 {
+                for j in 0..self.player_order.len() {
+                    if self.player_order[j] == i {
+                        self.player_order.remove(j);
+                        if j <= self.turn {
+                            self.turn -= 1;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let ([last], GameOutcome::InProgress) = (self.player_order.as_slice(), self.outcome) {
+            self.outcome = GameOutcome::Won {
+                player: *last,
+                goal: Goal::Eliminate,
+            };
+        }
+    }
+
+    /// ends the game if `player` met a win condition at the end of their turn
+    fn check_win(&mut self, player: usize) {
+        if self.outcome != GameOutcome::InProgress || !self.player_order.contains(&player) {
+            return;
+        }
+
+        let territories = self.count_territories()[player];
+        let met = self.win_conditions.iter().find(|condition| {
+            let applies = match condition.player {
+                Some(only) => only == player,
+                None => true,
+            };
+            applies
+                && match condition.goal {
+                    Goal::Eliminate => false,
+                    Goal::HoldTerritories(count) => territories >= count,
+                    // this is the player's turn number `round + 1`
+                    Goal::Survive(turns) => self.round + 1 >= turns,
+                }
+        });
+        if let Some(condition) = met {
+            self.outcome = GameOutcome::Won {
+                player,
+                goal: condition.goal,
+            };
+        }
+    }
+
+    pub fn outcome(&self) -> GameOutcome {
+        self.outcome
+    }
+
+    /// player who won the game, `None` while it's still going
+    pub fn winner(&self) -> Option<usize> {
+        match self.outcome {
+            GameOutcome::Won { player, .. } => Some(player),
+            GameOutcome::InProgress => None,
+        }
+    }
+
+    /// no more moves can be made once someone has won or only one player is left
+    pub fn is_over(&self) -> bool {
+        self.outcome != GameOutcome::InProgress || self.player_order.len() <= 1
+    }
+
+    /// territories that share a border with `territory`
+    pub fn connections(&self, territory: usize) -> &[usize] {
+        &self.territories[territory].connections
+    }
+
+    pub fn owner(&self, territory: usize) -> usize {
+        self.territories[territory].owner
+    }
+
+    pub fn current_player(&self) -> usize {
+        self.player_order[self.turn]
+    }
+
+    /// builds a board from a hand made map without checking it, use [Scenario::to_board] for
+    /// scenarios that need to be playable. players without territories are left out
+    pub fn from_scenario(scenario: &Scenario, seed: u64) -> Self {
+        let map = HexMap::new(
+            scenario
+                .territories
+                .iter()
+                .map(|territory| territory.tiles.clone())
+                .collect(),
+        );
+        let territories = scenario
+            .territories
+            .iter()
+            .zip(map.connections())
+            .map(|(territory, connections)| Territory {
+                owner: territory.owner,
+                dice: territory.dice,
+                connections,
+            })
+            .collect::<Vec<Territory>>();
+        let player_order = (0..scenario.player_count)
+            .filter(|player| territories.iter().any(|t| t.owner == *player))
+            .collect();
+
+        Self {
+            rules: scenario.rules,
+            turn: 0,
+            player_order,
+            territories,
+            stockpiles: vec![0; scenario.player_count],
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            map: Arc::new(map),
+            win_conditions: scenario.win_conditions.clone(),
+            round: 0,
+            outcome: GameOutcome::InProgress,
+        }
+    }
+
+    /// size of the largest group of connected territories owned by `player`, this is the number of
+    /// bonus dice they get at the end of their turn
+    pub fn largest_region(&self, player: usize) -> u32 {
+        largest_region(&self.territories, player)
+    }
+
+    /// how evenly territories, dice and largest regions are split between the players still in
+    /// the game, 1 is perfectly even and 0 is as uneven as it gets
+    pub fn fairness(&self) -> f32 {
+        let spread = |values: Vec<u32>| {
+            let max = values.iter().copied().max().unwrap_or(0);
+            let min = values.iter().copied().min().unwrap_or(0);
+            if max == 0 {
+                0.0
+            } else {
+                (max - min) as f32 / max as f32
+            }
+        };
+        let players = &self.player_order;
+        let owned = |player: usize| self.territories.iter().filter(move |t| t.owner == player);
+        let spreads = [
+            spread(players.iter().map(|p| owned(*p).count() as u32).collect()),
+            spread(
+                players
+                    .iter()
+                    .map(|p| owned(*p).map(|t| t.dice).sum())
+                    .collect(),
+            ),
+            spread(players.iter().map(|p| self.largest_region(*p)).collect()),
+        ];
+        1.0 - spreads.iter().sum::<f32>() / spreads.len() as f32
+    }
+
+    pub fn scores(&self) -> (usize, Vec<(usize, u32)>) {
+        let mut scores = Vec::new();
+        for player in self.player_order.iter() {
+            scores.push((*player, self.largest_region(*player)));
+        }
+        (self.turn, scores)
+    }
+}
+
+/// size of the biggest group of connected territories owned by `player`
+fn largest_region(territories: &[Territory], player: usize) -> u32 {
+    let mut visited = vec![false; territories.len()];
+    let mut largest = 0;
+    for start in 0..territories.len() {
+        if visited[start] || territories[start].owner != player {
+            continue;
+        }
+
+        // flood fill over connections
+        let mut size = 0;
+        let mut stack = vec![start];
+        visited[start] = true;
+        while let Some(current) = stack.pop() {
+            size += 1;
+            for neighbor in territories[current].connections.iter() {
+                if !visited[*neighbor] && territories[*neighbor].owner == player {
+                    visited[*neighbor] = true;
+                    stack.push(*neighbor);
+                }
+            }
+        }
+        largest = largest.max(size);
+    }
+    largest
+}
+
+/// whether every territory can be reached from every other one
+pub(crate) fn is_connected(connections: &[Vec<usize>]) -> bool {
+    if connections.is_empty() {
+        return true;
+    }
+    let mut visited = vec![false; connections.len()];
+    let mut stack = vec![0];
+    let mut count = 0;
+    while let Some(current) = stack.pop() {
+        if visited[current] {
+            continue;
+        }
+        visited[current] = true;
+        count += 1;
+        stack.extend(connections[current].iter().copied());
+    }
+    count == connections.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::ScenarioTerritory;
+
+    /// a row of one hex territories, each next to the ones before and after it
+    fn row(owners: &[usize], dice: &[u32]) -> Board {
+        let mut scenario = Scenario::new(owners.iter().max().unwrap() + 1, RuleSet::default());
+        scenario.territories = owners
+            .iter()
+            .zip(dice)
+            .enumerate()
+            .map(|(i, (owner, dice))| ScenarioTerritory {
+                tiles: vec![Hex::new(i as i32, 0, -(i as i32))],
+                owner: *owner,
+                dice: *dice,
+            })
+            .collect();
+        Board::from_scenario(&scenario, 0)
+    }
+
+    fn total_dice(board: &Board, player: usize) -> u32 {
+        board
+            .territories
+            .iter()
+            .filter(|territory| territory.owner == player)
+            .map(|territory| territory.dice)
+            .sum()
+    }
+
+    #[test]
+    fn largest_region_only_counts_connected_territories() {
+        let board = row(&[0, 0, 1, 0, 0, 0, 1], &[1; 7]);
+        assert_eq!(board.largest_region(0), 3);
+        assert_eq!(board.largest_region(1), 1);
+        assert_eq!(board.scores().1, vec![(0, 3), (1, 1)]);
+    }
+
+    #[test]
+    fn finish_turn_gives_the_largest_region_as_bonus() {
+        let mut board = row(&[0, 0, 1, 0, 0, 0, 1], &[1; 7]);
+        board.finish_turn();
+        assert_eq!(total_dice(&board, 0), 5 + 3);
+        assert_eq!(total_dice(&board, 1), 2);
+        assert_eq!(board.current_player(), 1);
+    }
+
+    #[test]
+    fn bonus_that_doesnt_fit_is_stockpiled() {
+        let mut board = row(&[0, 0, 1], &[8, 7, 1]);
+        board.finish_turn();
+        assert_eq!(board.territories[1].dice, 8);
+        assert_eq!(board.stockpiles[0], 1);
+    }
+
+    #[test]
+    fn stockpile_is_capped() {
+        let mut board = row(&[0, 0, 1], &[8, 8, 1]);
+        board.stockpiles[0] = MAX_STOCKPILE;
+        board.finish_turn();
+        assert_eq!(board.stockpiles[0], MAX_STOCKPILE);
+    }
+
+    #[test]
+    fn stockpile_is_spent_before_it_grows() {
+        let mut board = row(&[0, 0, 1], &[1, 1, 1]);
+        board.stockpiles[0] = 5;
+        board.finish_turn();
+        assert_eq!(total_dice(&board, 0), 2 + 5 + 2);
+        assert_eq!(board.stockpiles[0], 0);
+    }
+
+    #[test]
+    fn rolls_stay_within_the_die_faces() {
+        for die_faces in [2, 6, 20] {
+            for seed in 0..50 {
+                let mut board = row(&[0, 1], &[8, 8]);
+                board.rules.die_faces = die_faces;
+                board.rng = ChaCha8Rng::seed_from_u64(seed);
+                let outcome = board.try_attack(0, 1).unwrap();
+                assert_eq!(outcome.attacker_rolls.len(), 8);
+                assert_eq!(outcome.defender_rolls.len(), 8);
+                let mut rolls = outcome.attacker_rolls.iter().chain(&outcome.defender_rolls);
+                assert!(rolls.all(|roll| (1..=die_faces).contains(roll)));
+                assert_eq!(
+                    outcome.attacker_total,
+                    outcome.attacker_rolls.iter().sum::<u32>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ties_follow_the_tie_break() {
+        // one face means both sides always roll the same total
+        for (tie_break, win) in [(TieBreak::Defender, false), (TieBreak::Attacker, true)] {
+            let mut board = row(&[0, 1], &[3, 3]);
+            board.rules.die_faces = 1;
+            board.rules.tie_break = tie_break;
+            assert_eq!(board.try_attack(0, 1).unwrap().win, win);
+        }
+    }
+
+    #[test]
+    fn try_attack_rejects_each_kind_of_bad_move() {
+        let mut board = row(&[0, 0, 1, 1], &[3, 1, 2, 2]);
+        let cases = [
+            ((0, 4), MoveError::OutOfRange),
+            ((2, 1), MoveError::NotYourTerritory),
+            ((0, 1), MoveError::OwnTerritory),
+            ((0, 3), MoveError::NotAdjacent),
+            ((1, 2), MoveError::NotEnoughDice),
+        ];
+        for ((first, second), error) in cases {
+            assert_eq!(board.try_attack(first, second).unwrap_err(), error);
+        }
+        // nothing changes when a move is rejected
+        let dice = board.territories.iter().map(|t| t.dice).collect::<Vec<_>>();
+        assert_eq!(dice, vec![3, 1, 2, 2]);
+
+        let mut over = row(&[0, 0], &[3, 1]);
+        assert!(over.is_over());
+        assert_eq!(over.try_attack(0, 1).unwrap_err(), MoveError::GameOver);
+    }
+
+    /// plays the first move available until there are none, then ends the turn
+    fn play(board: &mut Board, steps: usize) -> Vec<AttackOutcome> {
+        let mut outcomes = Vec::new();
+        for _ in 0..steps {
+            match board.all_moves().first() {
+                Some((first, second)) => outcomes.push(board.try_attack(*first, *second).unwrap()),
+                None => board.finish_turn(),
+            }
+        }
+        outcomes
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_game() {
+        let settings = BoardGenSettings {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let mut first = Board::generate(&settings).unwrap();
+        let mut second = Board::generate(&settings).unwrap();
+        assert_eq!(first.seed, 42);
+        assert_eq!(first.map.territory_tiles, second.map.territory_tiles);
+
+        let first_rolls = play(&mut first, 200);
+        let second_rolls = play(&mut second, 200);
+        assert_eq!(format!("{:?}", first_rolls), format!("{:?}", second_rolls));
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn different_seeds_give_different_boards() {
+        let board = |seed| {
+            Board::generate(&BoardGenSettings {
+                seed: Some(seed),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        assert_ne!(board(1).map.territory_tiles, board(2).map.territory_tiles);
+    }
+
+    #[test]
+    fn validate_catches_settings_that_cant_work() {
+        let settings = BoardGenSettings::default();
+        assert_eq!(settings.validate(), Ok(()));
+
+        let with = |edit: fn(&mut BoardGenSettings)| {
+            let mut settings = settings.clone();
+            edit(&mut settings);
+            settings.validate()
+        };
+        assert_eq!(
+            with(|s| s.player_count = 1),
+            Err(GenerateError::PlayerCount(1))
+        );
+        assert_eq!(
+            with(|s| s.player_count = MAX_PLAYERS + 1),
+            Err(GenerateError::PlayerCount(MAX_PLAYERS + 1))
+        );
+        assert_eq!(
+            with(|s| {
+                s.player_count = 4;
+                s.num_territories = 3;
+            }),
+            Err(GenerateError::NotEnoughTerritories {
+                territories: 3,
+                players: 4,
+            })
+        );
+        assert!(matches!(
+            with(|s| s.board_size = 7),
+            Err(GenerateError::BoardTooSmall { .. })
+        ));
+        assert_eq!(
+            with(|s| s.average_dice = 0),
+            Err(GenerateError::Dice { average: 0, max: 8 })
+        );
+        assert_eq!(
+            with(|s| s.average_dice = 9),
+            Err(GenerateError::Dice { average: 9, max: 8 })
+        );
+        assert_eq!(
+            with(|s| s.rules.die_faces = 0),
+            Err(GenerateError::Rules {
+                die_faces: 0,
+                max_dice: 8
+            })
+        );
+    }
+
+    #[test]
+    fn default_boards_are_connected_and_even_for_every_player_count() {
+        for player_count in 2..=MAX_PLAYERS {
+            for seed in 0..5 {
+                let settings = BoardGenSettings {
+                    player_count,
+                    seed: Some(seed),
+                    ..Default::default()
+                };
+                let board = Board::generate(&settings).unwrap();
+                let connections = board
+                    .territories
+                    .iter()
+                    .map(|territory| territory.connections.clone())
+                    .collect::<Vec<_>>();
+                assert!(is_connected(&connections));
+
+                let counts = &board.count_territories()[..player_count];
+                assert!(
+                    counts.iter().all(|count| *count == counts[0]),
+                    "{:?}",
+                    counts
+                );
+                // every territory starts with one die, the rest are split evenly
+                let extra = (0..player_count)
+                    .map(|player| total_dice(&board, player) - counts[player])
+                    .collect::<Vec<_>>();
+                assert!(extra.iter().all(|dice| *dice == extra[0]), "{:?}", extra);
+            }
+        }
+    }
+
+    #[test]
+    fn unbalanced_boards_still_give_everyone_a_territory() {
+        for seed in 0..20 {
+            let settings = BoardGenSettings {
+                player_count: MAX_PLAYERS,
+                num_territories: MAX_PLAYERS,
+                balanced: false,
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let board = Board::generate(&settings).unwrap();
+            assert!(board.count_territories().iter().all(|count| *count > 0));
+        }
+    }
+
+    #[test]
+    fn starting_clusters_stay_within_the_limit() {
+        for seed in 0..5 {
+            let settings = BoardGenSettings {
+                max_starting_cluster: Some(2),
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let board = Board::generate(&settings).unwrap();
+            for player in 0..settings.player_count {
+                assert!(board.largest_region(player) <= 2);
+            }
+        }
+    }
+
+    #[test]
+    fn fairness_drops_as_the_board_gets_less_even() {
+        assert_eq!(row(&[0, 1], &[2, 2]).fairness(), 1.0);
+        let uneven = row(&[0, 0, 1], &[2, 2, 2]).fairness();
+        assert!(uneven < 1.0 && uneven > 0.0);
+    }
+
+    fn with_goal(mut board: Board, player: Option<usize>, goal: Goal) -> Board {
+        board.win_conditions = vec![WinCondition { player, goal }];
+        board
+    }
+
+    #[test]
+    fn eliminating_everyone_wins() {
+        let mut board = with_goal(row(&[0, 1], &[8, 1]), None, Goal::Eliminate);
+        // eight dice always beat one
+        assert!(board.try_attack(0, 1).unwrap().win);
+        assert_eq!(
+            board.outcome(),
+            GameOutcome::Won {
+                player: 0,
+                goal: Goal::Eliminate
+            }
+        );
+        assert!(board.is_over());
+    }
+
+    #[test]
+    fn holding_enough_territories_wins() {
+        let mut board = with_goal(
+            row(&[0, 0, 0, 1], &[1; 4]),
+            Some(0),
+            Goal::HoldTerritories(4),
+        );
+        board.finish_turn();
+        assert_eq!(board.outcome(), GameOutcome::InProgress);
+
+        let mut board = with_goal(
+            row(&[0, 0, 0, 1], &[1; 4]),
+            Some(0),
+            Goal::HoldTerritories(3),
+        );
+        board.finish_turn();
+        assert_eq!(
+            board.outcome(),
+            GameOutcome::Won {
+                player: 0,
+                goal: Goal::HoldTerritories(3)
+            }
+        );
+    }
+
+    #[test]
+    fn surviving_enough_turns_wins() {
+        let mut board = with_goal(row(&[0, 1], &[1, 1]), Some(1), Goal::Survive(2));
+        for _ in 0..3 {
+            board.finish_turn();
+            assert_eq!(board.winner(), None);
+        }
+        board.finish_turn();
+        assert_eq!(
+            board.outcome(),
+            GameOutcome::Won {
+                player: 1,
+                goal: Goal::Survive(2)
+            }
+        );
+    }
+
+    #[test]
+    fn goals_only_apply_to_their_player() {
+        let mut board = with_goal(row(&[0, 0, 1], &[1; 3]), Some(1), Goal::HoldTerritories(1));
+        board.finish_turn();
+        assert_eq!(board.winner(), None);
+        board.finish_turn();
+        assert_eq!(board.winner(), Some(1));
+    }
+
+    #[test]
+    fn clones_share_the_map() {
+        let board = row(&[0, 1], &[2, 2]);
+        assert!(Arc::ptr_eq(&board.map, &board.clone().map));
+    }
+
+    #[test]
+    fn count_territories_has_a_slot_for_every_player() {
+        let board = row(&[0, 1, 2, 3, 4, 5, 6, 7], &[1; 8]);
+        assert_eq!(board.count_territories(), vec![1; MAX_PLAYERS]);
+    }
+
+    #[test]
+    fn knocking_out_a_player_takes_them_out_of_the_turn_order() {
+        let mut board = row(&[0, 1, 2], &[8, 1, 1]);
+        board.try_attack(0, 1).unwrap();
+        assert_eq!(board.player_order, vec![0, 2]);
+        assert_eq!(board.current_player(), 0);
+        assert!(!board.is_over());
+        board.finish_turn();
+        assert_eq!(board.current_player(), 2);
+    }
+}
@@ -0,0 +1,360 @@
+use super::{
+    agent::{Action, Agent},
+    board::{Board, RuleSet, TieBreak},
+};
+use instant::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// exact chance of an attack succeeding for every combination of attacking and defending dice
+#[derive(Debug, Clone)]
+pub struct WinTable {
+    rules: RuleSet,
+    /// indexed by [attacker dice - 1][defender dice - 1]
+    probabilities: Vec<Vec<f32>>,
+}
+
+impl WinTable {
+    pub fn new(rules: &RuleSet) -> Self {
+        let faces = rules.die_faces as usize;
+        let max_dice = rules.max_dice as usize;
+
+        // distributions[n][s] is the chance of rolling a total of s with n dice
+        let mut distributions = vec![vec![1.0f64]];
+        for n in 1..=max_dice {
+            let previous = &distributions[n - 1];
+            let mut distribution = vec![0.0; n * faces + 1];
+            for (total, chance) in previous.iter().enumerate() {
+                for face in 1..=faces {
+                    distribution[total + face] += chance / faces as f64;
+                }
+            }
+            distributions.push(distribution);
+        }
+
+        let tie_chance = match rules.tie_break {
+            TieBreak::Defender => 0.0,
+            TieBreak::Attacker => 1.0,
+            TieBreak::Random => 0.5,
+        };
+
+        let mut probabilities = vec![vec![0.0; max_dice]; max_dice];
+        for attacker in 1..=max_dice {
+            for defender in 1..=max_dice {
+                // chance of the defender rolling at most each total
+                let mut cumulative = 0.0;
+                let mut win = 0.0;
+                for (total, chance) in distributions[defender].iter().enumerate() {
+                    if let Some(attacker_chance) = distributions[attacker].get(total) {
+                        win += attacker_chance * (cumulative + chance * tie_chance);
+                    }
+                    cumulative += chance;
+                }
+                // attacker totals above the defender's highest possible total always win
+                win += distributions[attacker]
+                    .iter()
+                    .skip(distributions[defender].len())
+                    .sum::<f64>();
+                probabilities[attacker - 1][defender - 1] = win as f32;
+            }
+        }
+
+        Self {
+            rules: *rules,
+            probabilities,
+        }
+    }
+
+    /// chance of `attacker` dice beating `defender` dice, counts above the dice cap are clamped
+    pub fn win(&self, attacker: u32, defender: u32) -> f32 {
+        let max = self.probabilities.len();
+        let attacker = (attacker as usize).clamp(1, max);
+        let defender = (defender as usize).clamp(1, max);
+        self.probabilities[attacker - 1][defender - 1]
+    }
+}
+
+/// how many positions the [ExpectimaxAgent] is allowed to look at per action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    /// search budget in positions. the game also stops the search at [ExpectimaxAgent::FRAME_TIME]
+    /// in case the budget takes longer than that on a slow machine
+    pub fn budget(&self) -> usize {
+        match self {
+            Difficulty::Easy => 30,
+            Difficulty::Normal => 200,
+            Difficulty::Hard => 1000,
+        }
+    }
+}
+
+/// the parts of the board an attack changes, searched instead of cloning the whole [Board]
+struct Position {
+    owners: Vec<usize>,
+    dice: Vec<u32>,
+}
+
+impl Position {
+    fn new(board: &Board) -> Self {
+        Self {
+            owners: board.territories.iter().map(|t| t.owner).collect(),
+            dice: board.territories.iter().map(|t| t.dice).collect(),
+        }
+    }
+
+    /// same as [Board::resolve_attack], returns what's needed to [Position::undo] it
+    fn attack(&mut self, first: usize, second: usize, win: bool) -> (usize, u32, u32) {
+        let undo = (self.owners[second], self.dice[first], self.dice[second]);
+        if win {
+            self.owners[second] = self.owners[first];
+            self.dice[second] = self.dice[first] - 1;
+        }
+        self.dice[first] = 1;
+        undo
+    }
+
+    fn undo(&mut self, first: usize, second: usize, undo: (usize, u32, u32)) {
+        let (owner, first_dice, second_dice) = undo;
+        self.owners[second] = owner;
+        self.dice[first] = first_dice;
+        self.dice[second] = second_dice;
+    }
+
+    /// same as [Board::largest_region] for this position
+    fn largest_region(&self, board: &Board, player: usize) -> u32 {
+        let mut visited = vec![false; self.owners.len()];
+        let mut largest = 0;
+        for start in 0..self.owners.len() {
+            if visited[start] || self.owners[start] != player {
+                continue;
+            }
+            let mut size = 0;
+            let mut stack = vec![start];
+            visited[start] = true;
+            while let Some(current) = stack.pop() {
+                size += 1;
+                for neighbor in board.connections(current) {
+                    if !visited[*neighbor] && self.owners[*neighbor] == player {
+                        visited[*neighbor] = true;
+                        stack.push(*neighbor);
+                    }
+                }
+            }
+            largest = largest.max(size);
+        }
+        largest
+    }
+}
+
+/// looks ahead over sequences of attacks, weighting each one by its exact chance of success, and
+/// scores the position it would end its turn in including the reinforcement it would get
+pub struct ExpectimaxAgent {
+    budget: usize,
+    /// the search gives up once this much time has passed, `None` to only use the budget
+    time_limit: Option<Duration>,
+    nodes: usize,
+    deadline: Option<Instant>,
+    table: Option<WinTable>,
+}
+
+impl ExpectimaxAgent {
+    /// number of attacks considered at each step, best chance of winning first
+    const BRANCHING: usize = 6;
+    /// attacks less likely than this to succeed are never considered
+    const MIN_CHANCE: f32 = 0.2;
+    /// the clock is only read this often since it's slow on the web
+    const CLOCK_NODES: usize = 16;
+    /// time limit that lets the game ask for an action every frame, half a frame at 60 fps
+    pub const FRAME_TIME: Duration = Duration::from_millis(8);
+
+    /// without a `time_limit` the same position always gives the same action, with one the search
+    /// can stop early on a slow machine
+    pub fn new(budget: usize, time_limit: Option<Duration>) -> Self {
+        Self {
+            budget,
+            time_limit,
+            nodes: 0,
+            deadline: None,
+            table: None,
+        }
+    }
+
+    fn table(&self) -> &WinTable {
+        self.table.as_ref().unwrap()
+    }
+
+    /// heuristic value of the position for `player` once they end their turn
+    fn evaluate(&self, board: &Board, position: &Position, player: usize) -> f32 {
+        let mut value = 0.0;
+        let mut dice = 0;
+        let mut room = 0;
+        for i in 0..position.owners.len() {
+            if position.owners[i] != player {
+                continue;
+            }
+            dice += position.dice[i];
+            room += board.rules.max_dice.saturating_sub(position.dice[i]);
+            value += 1.0;
+
+            // chance of losing the territory to its strongest neighbour next turn
+            let threat = board
+                .connections(i)
+                .iter()
+                .filter(|neighbor| position.owners[**neighbor] != player)
+                .map(|neighbor| position.dice[*neighbor])
+                .max();
+            if let Some(threat) = threat {
+                if threat > 1 {
+                    value -= self.table().win(threat, position.dice[i]);
+                }
+            }
+        }
+
+        // reinforcement step, the largest region's worth of dice plus the stockpile where they fit
+        let region = position.largest_region(board, player);
+        let stockpile = board.stockpiles.get(player).copied().unwrap_or(0);
+        let bonus = (region + stockpile).min(room);
+
+        value + region as f32 * 1.5 + (dice + bonus) as f32 * 0.3
+    }
+
+    /// whether the budget or time limit has run out
+    fn out_of_time(&self) -> bool {
+        if self.nodes > self.budget {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) => {
+                self.nodes.is_multiple_of(Self::CLOCK_NODES) && Instant::now() >= deadline
+            }
+            None => false,
+        }
+    }
+
+    /// best action and its expected value, `None` if the search ran out of time before finishing.
+    /// `position` is put back the way it was before returning
+    fn search(
+        &mut self,
+        board: &Board,
+        position: &mut Position,
+        player: usize,
+        depth: u32,
+    ) -> Option<(f32, Action)> {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return None;
+        }
+
+        let mut best = (self.evaluate(board, position, player), Action::EndTurn);
+        if depth == 0 {
+            return Some(best);
+        }
+
+        let mut moves = Vec::new();
+        for first in 0..position.owners.len() {
+            if position.owners[first] != player || position.dice[first] <= 1 {
+                continue;
+            }
+            for second in board.connections(first) {
+                if position.owners[*second] == player {
+                    continue;
+                }
+                let chance = self
+                    .table()
+                    .win(position.dice[first], position.dice[*second]);
+                if chance >= Self::MIN_CHANCE {
+                    moves.push((chance, first, *second));
+                }
+            }
+        }
+        moves.sort_by(|a, b| b.0.total_cmp(&a.0));
+        moves.truncate(Self::BRANCHING);
+
+        for (chance, first, second) in moves {
+            let undo = position.attack(first, second, true);
+            let win_value = self.search(board, position, player, depth - 1);
+            position.undo(first, second, undo);
+            let (win_value, _) = win_value?;
+
+            let undo = position.attack(first, second, false);
+            let loss_value = self.search(board, position, player, depth - 1);
+            position.undo(first, second, undo);
+            let (loss_value, _) = loss_value?;
+
+            let value = chance * win_value + (1.0 - chance) * loss_value;
+            if value > best.0 {
+                best = (value, Action::Attack(first, second));
+            }
+        }
+        Some(best)
+    }
+}
+
+impl Agent for ExpectimaxAgent {
+    fn next_action(&mut self, board: &Board) -> Action {
+        let stale = match &self.table {
+            Some(table) => table.rules != board.rules,
+            None => true,
+        };
+        if stale {
+            self.table = Some(WinTable::new(&board.rules));
+        }
+
+        // search deeper until the budget runs out, keeping the last search that finished
+        let player = board.current_player();
+        let mut position = Position::new(board);
+        let mut action = Action::EndTurn;
+        self.nodes = 0;
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        for depth in 1.. {
+            match self.search(board, &mut position, player, depth) {
+                Some((_, best)) => action = best,
+                None => break,
+            }
+            // nothing left to look at
+            if board.all_moves().is_empty() {
+                break;
+            }
+        }
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardGenSettings;
+
+    #[test]
+    fn win_table_matches_the_exact_odds() {
+        let table = WinTable::new(&RuleSet::CLASSIC);
+        // one die against one wins 15 of the 36 rolls when the defender takes ties
+        assert!((table.win(1, 1) - 15.0 / 36.0).abs() < 1e-6);
+        assert!(table.win(8, 1) > 0.99);
+        assert_eq!(table.win(20, 1), table.win(8, 1));
+    }
+
+    #[test]
+    fn search_stops_at_the_time_limit() {
+        let board = Board::generate(&BoardGenSettings {
+            seed: Some(3),
+            player_count: 4,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut agent = ExpectimaxAgent::new(usize::MAX, Some(Duration::from_millis(10)));
+        let start = Instant::now();
+        let action = agent.next_action(&board);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        if let Action::Attack(first, second) = action {
+            assert!(board.validate_attack(first, second).is_ok());
+        }
+    }
+}
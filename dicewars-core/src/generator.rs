@@ -0,0 +1,608 @@
+use super::{
+    board::{BoardGenSettings, GenerateError},
+    hex::Hex,
+};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+/// decides which hexes make up each territory
+pub trait MapGenerator {
+    /// territories as lists of hexes, every hex must be closer than `board_size` to the center.
+    /// can return fewer than `num_territories` if they didn't fit
+    fn generate(&self, settings: &BoardGenSettings, rng: &mut ChaCha8Rng) -> Vec<Vec<Hex>>;
+
+    /// checks settings that only this generator cares about
+    fn validate(&self, _settings: &BoardGenSettings) -> Result<(), GenerateError> {
+        Ok(())
+    }
+
+    /// number of rotated copies the map is made of. territory `i` is a copy of territory
+    /// `i - i % symmetry` so each player can be given one copy
+    fn symmetry(&self) -> usize {
+        1
+    }
+}
+
+/// the map generators and their settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GeneratorSettings {
+    Grow(GrowGenerator),
+    Voronoi(VoronoiGenerator),
+    Islands(IslandGenerator),
+    Symmetric(SymmetricGenerator),
+}
+
+impl GeneratorSettings {
+    pub const ALL: [GeneratorSettings; 4] = [
+        GeneratorSettings::Grow(GrowGenerator::DEFAULT),
+        GeneratorSettings::Voronoi(VoronoiGenerator::DEFAULT),
+        GeneratorSettings::Islands(IslandGenerator::DEFAULT),
+        GeneratorSettings::Symmetric(SymmetricGenerator::DEFAULT),
+    ];
+
+    pub fn generator(&self) -> &dyn MapGenerator {
+        match self {
+            GeneratorSettings::Grow(generator) => generator,
+            GeneratorSettings::Voronoi(generator) => generator,
+            GeneratorSettings::Islands(generator) => generator,
+            GeneratorSettings::Symmetric(generator) => generator,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorSettings::Grow(_) => "Grow",
+            GeneratorSettings::Voronoi(_) => "Voronoi",
+            GeneratorSettings::Islands(_) => "Islands",
+            GeneratorSettings::Symmetric(_) => "Symmetric",
+        }
+    }
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings::Grow(GrowGenerator::DEFAULT)
+    }
+}
+
+/// free hexes next to the given territories that are still on the board
+fn expand_options(
+    territories: &[Vec<Hex>],
+    map: &HashMap<Hex, usize>,
+    board_size: usize,
+) -> Vec<Hex> {
+    let mut options = Vec::new();
+    for territory in territories {
+        for tile in territory.iter() {
+            for neighbor in Hex::orthogonal() {
+                let neighbor = *tile + neighbor;
+                if !map.contains_key(&neighbor) && neighbor.distance(Hex::ZERO) < board_size as i32
+                {
+                    options.push(neighbor);
+                }
+            }
+        }
+    }
+    options
+}
+
+/// keeps only the biggest group of territories that touch each other, so every territory can be
+/// reached from every other one
+fn largest_component(territory_tiles: Vec<Vec<Hex>>) -> Vec<Vec<Hex>> {
+    let mut map = HashMap::new();
+    for (index, territory) in territory_tiles.iter().enumerate() {
+        for tile in territory {
+            map.insert(*tile, index);
+        }
+    }
+
+    let mut component = vec![usize::MAX; territory_tiles.len()];
+    let mut sizes = Vec::new();
+    for start in 0..territory_tiles.len() {
+        if component[start] != usize::MAX {
+            continue;
+        }
+        let id = sizes.len();
+        let mut size = 0;
+        let mut stack = vec![start];
+        component[start] = id;
+        while let Some(current) = stack.pop() {
+            size += 1;
+            for tile in territory_tiles[current].iter() {
+                for direction in Hex::orthogonal() {
+                    if let Some(neighbor) = map.get(&(*tile + direction)) {
+                        if component[*neighbor] == usize::MAX {
+                            component[*neighbor] = id;
+                            stack.push(*neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    let largest = (0..sizes.len()).max_by_key(|id| sizes[*id]);
+    territory_tiles
+        .into_iter()
+        .zip(component)
+        .filter(|(_, id)| Some(*id) == largest)
+        .map(|(territory, _)| territory)
+        .collect()
+}
+
+/// splits `land` into territories around `seeds`, each hex goes to the seed it can walk to in the
+/// fewest steps without leaving the land
+fn flood_territories(land: &[Hex], seeds: &[Hex]) -> Vec<Vec<Hex>> {
+    let mut owner = HashMap::new();
+    for hex in land {
+        owner.insert(*hex, usize::MAX);
+    }
+
+    let mut queue = VecDeque::new();
+    for (index, seed) in seeds.iter().enumerate() {
+        owner.insert(*seed, index);
+        queue.push_back(*seed);
+    }
+    while let Some(hex) = queue.pop_front() {
+        let index = owner[&hex];
+        for direction in Hex::orthogonal() {
+            let neighbor = hex + direction;
+            if owner.get(&neighbor) == Some(&usize::MAX) {
+                owner.insert(neighbor, index);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let mut territories = vec![Vec::new(); seeds.len()];
+    for hex in land {
+        if let Some(index) = owner.get(hex).filter(|index| **index != usize::MAX) {
+            territories[*index].push(*hex);
+        }
+    }
+    territories
+}
+
+/// picks up to `count` hexes from `options` that are at least `spacing` apart. with `reach` every
+/// seed after the first is also within that distance of an earlier one so the seeds stay together
+fn spaced_seeds(
+    options: &[Hex],
+    count: usize,
+    spacing: i32,
+    reach: Option<i32>,
+    rng: &mut ChaCha8Rng,
+) -> Vec<Hex> {
+    let mut shuffled = options.to_vec();
+    shuffled.shuffle(rng);
+    let mut seeds: Vec<Hex> = Vec::new();
+    // keep going over the options since a seed can bring others into reach
+    let mut added = true;
+    while added && seeds.len() < count {
+        added = false;
+        for hex in shuffled.iter() {
+            if seeds.len() >= count {
+                break;
+            }
+            let spaced = seeds.iter().all(|seed| seed.distance(*hex) >= spacing);
+            let in_reach = match reach {
+                Some(reach) => {
+                    seeds.is_empty() || seeds.iter().any(|seed| seed.distance(*hex) <= reach)
+                }
+                None => true,
+            };
+            if spaced && in_reach {
+                seeds.push(*hex);
+                added = true;
+            }
+        }
+    }
+    seeds
+}
+
+/// grows each territory one hex at a time from a random spot next to the existing ones, this is
+/// the original generator
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GrowGenerator;
+
+impl GrowGenerator {
+    pub const DEFAULT: GrowGenerator = GrowGenerator;
+}
+
+impl MapGenerator for GrowGenerator {
+    fn generate(&self, settings: &BoardGenSettings, rng: &mut ChaCha8Rng) -> Vec<Vec<Hex>> {
+        let board_size = settings.board_size;
+        let mut map = HashMap::new();
+        let mut territory_tiles: Vec<Vec<Hex>> = Vec::new();
+
+        // territories that get stuck are thrown away, so allow a few extra tries
+        let mut i = 0;
+        'outer: for _ in 0..settings.num_territories * 2 {
+            if i == settings.num_territories {
+                break;
+            }
+
+            // create new territory
+            let options = if i == 0 {
+                vec![Hex::new(0, 0, 0)]
+            } else {
+                expand_options(&territory_tiles, &map, board_size)
+            };
+
+            if let Some(tile) = options.choose(rng) {
+                territory_tiles.push(vec![*tile]);
+                map.insert(*tile, i);
+            } else {
+                break;
+            }
+
+            // expand territory one tile at a time
+            for _ in 0..settings.territory_size {
+                let options = expand_options(&territory_tiles[i..=i], &map, board_size);
+                if let Some(tile) = options.choose(rng) {
+                    territory_tiles[i].push(*tile);
+                    map.insert(*tile, i);
+                } else {
+                    // discard if territory can no longer expand
+                    while let Some(tile) = territory_tiles[i].pop() {
+                        map.remove(&tile);
+                    }
+                    let _ = territory_tiles.pop();
+
+                    continue 'outer;
+                }
+            }
+
+            // expand whole territory to make it smoother
+            let options = expand_options(&territory_tiles[i..=i], &map, board_size);
+            for tile in options {
+                if let Entry::Vacant(entry) = map.entry(tile) {
+                    entry.insert(i);
+                    territory_tiles[i].push(tile);
+                }
+            }
+
+            i += 1;
+        }
+
+        territory_tiles
+    }
+}
+
+/// how far apart territory seeds are placed so territories end up roughly `territory_size` big
+fn seed_spacing(settings: &BoardGenSettings) -> i32 {
+    (settings.territory_size as f32).sqrt() as i32 + 1
+}
+
+/// scatters seeds over the board and gives every hex near a seed to the closest one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VoronoiGenerator {
+    /// hexes further than this from every seed are left empty
+    pub radius: i32,
+}
+
+impl VoronoiGenerator {
+    pub const DEFAULT: VoronoiGenerator = VoronoiGenerator { radius: 3 };
+}
+
+impl MapGenerator for VoronoiGenerator {
+    fn generate(&self, settings: &BoardGenSettings, rng: &mut ChaCha8Rng) -> Vec<Vec<Hex>> {
+        let cells = Hex::disc(settings.board_size as i32);
+        let seeds = spaced_seeds(
+            &cells,
+            settings.num_territories,
+            seed_spacing(settings),
+            Some(self.radius * 2),
+            rng,
+        );
+
+        let mut territory_tiles = vec![Vec::new(); seeds.len()];
+        for hex in cells {
+            // ties go to the first seed so the result doesn't depend on iteration order
+            let closest = seeds
+                .iter()
+                .enumerate()
+                .map(|(index, seed)| (seed.distance(hex), index))
+                .min();
+            if let Some((distance, index)) = closest {
+                if distance <= self.radius {
+                    territory_tiles[index].push(hex);
+                }
+            }
+        }
+
+        largest_component(territory_tiles)
+    }
+}
+
+/// smooth random noise decides what is land and what is water, the biggest island is then split
+/// into territories
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IslandGenerator {
+    /// size of the noise features in hexes, bigger makes fewer and larger lakes and bays
+    pub noise_scale: f32,
+    /// higher means more water, from -1 to 1
+    pub water_level: f32,
+}
+
+impl IslandGenerator {
+    pub const DEFAULT: IslandGenerator = IslandGenerator {
+        noise_scale: 6.0,
+        water_level: 0.0,
+    };
+
+    /// a few octaves of value noise between -1 and 1
+    fn noise(lattices: &[Vec<Vec<f32>>], x: f32, y: f32) -> f32 {
+        let mut value = 0.0;
+        let mut amplitude = 0.5;
+        let mut frequency = 1.0;
+        for lattice in lattices {
+            let (x, y) = (x * frequency, y * frequency);
+            let (cell_x, cell_y) = (x.floor() as usize, y.floor() as usize);
+            let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+            let (tx, ty) = (smooth(x.fract()), smooth(y.fract()));
+
+            let corner = |dx: usize, dy: usize| lattice[cell_y + dy][cell_x + dx];
+            let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+            let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+            value += (top + (bottom - top) * ty) * amplitude;
+
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        value / 0.875
+    }
+}
+
+impl MapGenerator for IslandGenerator {
+    fn generate(&self, settings: &BoardGenSettings, rng: &mut ChaCha8Rng) -> Vec<Vec<Hex>> {
+        const OCTAVES: u32 = 3;
+        let board_size = settings.board_size;
+        let cells = Hex::disc(board_size as i32);
+        let radius = board_size as f32 * 2.0;
+        let scale = self.noise_scale.max(1.0);
+
+        // random values on a square grid for each octave, big enough to cover the board
+        let lattices = (0..OCTAVES)
+            .map(|octave| {
+                let size = (radius * 2.0 / scale * 2f32.powi(octave as i32)).ceil() as usize + 3;
+                (0..size)
+                    .map(|_| (0..size).map(|_| rng.gen_range(-1.0..1.0)).collect())
+                    .collect::<Vec<Vec<f32>>>()
+            })
+            .collect::<Vec<_>>();
+
+        let land = cells
+            .into_iter()
+            .filter(|hex| {
+                let (x, y) = hex.to_grid();
+                let noise = Self::noise(&lattices, (x + radius) / scale, (y + radius) / scale);
+                // fade into water towards the edge of the board
+                let edge = hex.distance(Hex::ZERO) as f32 / board_size as f32;
+                noise + 0.5 - edge.powi(4) > self.water_level
+            })
+            .collect::<Vec<_>>();
+
+        let seeds = spaced_seeds(
+            &land,
+            settings.num_territories,
+            seed_spacing(settings),
+            None,
+            rng,
+        );
+        let territory_tiles = flood_territories(&land, &seeds)
+            .into_iter()
+            // slivers of land between bays aren't worth fighting over
+            .filter(|territory| territory.len() >= 3)
+            .collect();
+
+        largest_component(territory_tiles)
+    }
+}
+
+/// builds one slice of the board and rotates copies of it around the center so every player can
+/// start with the same shape of land
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SymmetricGenerator {
+    /// number of rotated copies, 2, 3 or 6
+    pub folds: usize,
+}
+
+impl SymmetricGenerator {
+    pub const DEFAULT: SymmetricGenerator = SymmetricGenerator { folds: 3 };
+
+    /// `hex` followed by its copies in the other slices, `None` if any of them collide with each
+    /// other, the map or the edge of the board
+    fn copies(&self, hex: Hex, map: &HashMap<Hex, usize>, board_size: usize) -> Option<Vec<Hex>> {
+        let folds = match self.folds {
+            2 | 3 | 6 => self.folds,
+            _ => 3,
+        };
+        let mut copies = vec![hex];
+        for _ in 1..folds {
+            let mut next = *copies.last().unwrap();
+            for _ in 0..6 / folds {
+                next = next.rotate();
+            }
+            copies.push(next);
+        }
+
+        let valid = copies.iter().enumerate().all(|(i, copy)| {
+            !map.contains_key(copy)
+                && copy.distance(Hex::ZERO) < board_size as i32
+                && !copies[..i].contains(copy)
+        });
+        valid.then_some(copies)
+    }
+}
+
+impl MapGenerator for SymmetricGenerator {
+    fn generate(&self, settings: &BoardGenSettings, rng: &mut ChaCha8Rng) -> Vec<Vec<Hex>> {
+        let board_size = settings.board_size;
+        let folds = self.symmetry();
+        let mut map = HashMap::new();
+        let mut territory_tiles: Vec<Vec<Hex>> = Vec::new();
+
+        // adds a hex and its copies to the last `folds` territories
+        let add = |hexes: Vec<Hex>, map: &mut HashMap<Hex, usize>, tiles: &mut Vec<Vec<Hex>>| {
+            let first = tiles.len() - folds;
+            for (i, hex) in hexes.into_iter().enumerate() {
+                tiles[first + i].push(hex);
+                map.insert(hex, first + i);
+            }
+        };
+
+        // territories that get stuck are thrown away, so allow a few extra tries
+        'outer: for _ in 0..settings.num_territories / folds * 2 {
+            if territory_tiles.len() == settings.num_territories / folds * folds {
+                break;
+            }
+
+            // start next to the center for the first territory, next to the others after that
+            let options = if territory_tiles.is_empty() {
+                Hex::orthogonal().to_vec()
+            } else {
+                expand_options(&territory_tiles, &map, board_size)
+            };
+            let options = options
+                .into_iter()
+                .filter_map(|hex| self.copies(hex, &map, board_size))
+                .collect::<Vec<_>>();
+            match options.choose(rng) {
+                Some(copies) => {
+                    territory_tiles.extend(vec![Vec::new(); folds]);
+                    add(copies.clone(), &mut map, &mut territory_tiles);
+                }
+                None => break,
+            }
+
+            // grow the first copy and mirror every hex it gets
+            let base = territory_tiles.len() - folds;
+            for step in 0..=settings.territory_size {
+                let options = expand_options(&territory_tiles[base..=base], &map, board_size)
+                    .into_iter()
+                    .filter_map(|hex| self.copies(hex, &map, board_size))
+                    .collect::<Vec<_>>();
+                // the last step only smooths the territory, so it's fine if there's no room
+                if step == settings.territory_size {
+                    for copies in options {
+                        if self.copies(copies[0], &map, board_size).is_some() {
+                            add(copies, &mut map, &mut territory_tiles);
+                        }
+                    }
+                } else if let Some(copies) = options.choose(rng) {
+                    add(copies.clone(), &mut map, &mut territory_tiles);
+                } else {
+                    // discard if territory can no longer expand
+                    for territory in territory_tiles.drain(base..) {
+                        for tile in territory {
+                            map.remove(&tile);
+                        }
+                    }
+                    continue 'outer;
+                }
+            }
+        }
+
+        territory_tiles
+    }
+
+    fn validate(&self, settings: &BoardGenSettings) -> Result<(), GenerateError> {
+        if !settings.num_territories.is_multiple_of(self.symmetry()) {
+            return Err(GenerateError::NotSymmetric {
+                territories: settings.num_territories,
+                folds: self.symmetry(),
+            });
+        }
+        Ok(())
+    }
+
+    fn symmetry(&self) -> usize {
+        match self.folds {
+            2 | 3 | 6 => self.folds,
+            _ => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn generators_stay_on_the_board_without_overlapping() {
+        let settings = BoardGenSettings::default();
+        for generator in GeneratorSettings::ALL {
+            for seed in 0..5 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let territories = generator.generator().generate(&settings, &mut rng);
+                assert!(!territories.is_empty(), "{}", generator.name());
+
+                let mut seen = HashSet::new();
+                for hex in territories.iter().flatten() {
+                    assert!(hex.distance(Hex::ZERO) < settings.board_size as i32);
+                    assert!(seen.insert(*hex), "{} reused a hex", generator.name());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_territories_are_rotated_copies() {
+        let generator = SymmetricGenerator::DEFAULT;
+        let folds = generator.symmetry();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let territories = generator.generate(&BoardGenSettings::default(), &mut rng);
+        assert_eq!(territories.len() % folds, 0);
+
+        let rotate = |hex: &Hex| (0..6 / folds).fold(*hex, |hex, _| hex.rotate());
+        for copies in territories.chunks(folds) {
+            for (territory, next) in copies.iter().zip(&copies[1..]) {
+                assert_eq!(&territory.iter().map(rotate).collect::<Vec<_>>(), next);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_maps_need_territories_for_every_copy() {
+        let settings = BoardGenSettings {
+            num_territories: 25,
+            ..Default::default()
+        };
+        assert_eq!(
+            SymmetricGenerator::DEFAULT.validate(&settings),
+            Err(GenerateError::NotSymmetric {
+                territories: 25,
+                folds: 3
+            })
+        );
+        assert_eq!(GrowGenerator::DEFAULT.validate(&settings), Ok(()));
+    }
+
+    #[test]
+    fn islands_cover_every_slider_setting() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for board_size in (7..=50).step_by(3).chain([50]) {
+            let settings = BoardGenSettings {
+                board_size,
+                ..Default::default()
+            };
+            // uneven steps so the scale doesn't only land on whole numbers
+            let scales = (0..=14).map(|i| 1.0 + i as f32 * 1.3).chain([20.0]);
+            for noise_scale in scales {
+                let generator = IslandGenerator {
+                    noise_scale,
+                    ..IslandGenerator::DEFAULT
+                };
+                for territory in generator.generate(&settings, &mut rng) {
+                    assert!(territory
+                        .iter()
+                        .all(|hex| hex.distance(Hex::ZERO) < board_size as i32));
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Hex {
     pub q: i32,
     pub r: i32,
@@ -14,12 +14,13 @@ impl Hex {
         Self { q, r, s }
     }
 
-    pub fn to_grid(&self) -> Vec2 {
+    /// position of the center of the hex as (x, y)
+    pub fn to_grid(&self) -> (f32, f32) {
         let sqrt3 = 3.0f32.sqrt();
         let x = sqrt3 * self.q as f32 + sqrt3 / 2.0 * self.r as f32;
         let y = (3.0 / 2.0) * self.r as f32;
 
-        return Vec2::new(x, y);
+        (x, y)
     }
 
     pub fn distance(&self, other: Self) -> i32 {
@@ -27,7 +28,23 @@ impl Hex {
         let d_r = (self.r - other.r).abs();
         let d_s = (self.s - other.s).abs();
 
-        return (d_q + d_r + d_s) / 2;
+        (d_q + d_r + d_s) / 2
+    }
+
+    /// rotates the hex 60 degrees around the center
+    pub const fn rotate(&self) -> Self {
+        Self::new(-self.r, -self.s, -self.q)
+    }
+
+    /// every hex closer than `radius` to the center
+    pub fn disc(radius: i32) -> Vec<Hex> {
+        let mut hexes = Vec::new();
+        for q in -radius + 1..radius {
+            for r in (-radius + 1).max(-q - radius + 1)..radius.min(-q + radius) {
+                hexes.push(Hex::new(q, r, -q - r));
+            }
+        }
+        hexes
     }
 
     pub const fn orthogonal() -> [Hex; 6] {
@@ -112,4 +129,4 @@ impl std::ops::Add for Hex {
 //             })
 //             .insert(TmpMapTile);
 //     }
-// }
\ No newline at end of file
+// }
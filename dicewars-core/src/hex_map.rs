@@ -0,0 +1,120 @@
+use super::hex::Hex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// layout of the board, which hexes make up each territory. only the territory tile lists are
+/// saved, everything else is rebuilt from them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<Vec<Hex>>", into = "Vec<Vec<Hex>>")]
+pub struct HexMap {
+    /// every hex on the board and the territory it belongs to
+    pub tiles: HashMap<Hex, usize>,
+    /// hexes belonging to each territory
+    pub territory_tiles: Vec<Vec<Hex>>,
+    /// average position of each territory's hexes in grid space, see [Hex::to_grid]
+    pub centroids: Vec<(f32, f32)>,
+}
+
+impl HexMap {
+    pub fn new(territory_tiles: Vec<Vec<Hex>>) -> Self {
+        let mut tiles = HashMap::new();
+        let mut centroids = Vec::new();
+        for (index, territory) in territory_tiles.iter().enumerate() {
+            let (mut x, mut y) = (0.0, 0.0);
+            for tile in territory {
+                tiles.insert(*tile, index);
+                let (tile_x, tile_y) = tile.to_grid();
+                x += tile_x;
+                y += tile_y;
+            }
+            let len = territory.len().max(1) as f32;
+            centroids.push((x / len, y / len));
+        }
+
+        Self {
+            tiles,
+            territory_tiles,
+            centroids,
+        }
+    }
+
+    pub fn territory_at(&self, hex: Hex) -> Option<usize> {
+        self.tiles.get(&hex).copied()
+    }
+
+    /// directions from `hex` that cross into another territory or off the board
+    pub fn borders(&self, hex: Hex) -> Vec<Hex> {
+        let territory = self.territory_at(hex);
+        Hex::orthogonal()
+            .into_iter()
+            .filter(|direction| self.territory_at(hex + *direction) != territory)
+            .collect()
+    }
+
+    /// territories that share an edge with each territory
+    pub fn connections(&self) -> Vec<Vec<usize>> {
+        let mut connections = vec![Vec::new(); self.territory_tiles.len()];
+        for (territory, tiles) in self.territory_tiles.iter().enumerate() {
+            for hex in tiles {
+                for direction in Hex::orthogonal() {
+                    if let Some(neighbor) = self.territory_at(*hex + direction) {
+                        if neighbor != territory && !connections[territory].contains(&neighbor) {
+                            connections[territory].push(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        connections
+    }
+}
+
+impl From<Vec<Vec<Hex>>> for HexMap {
+    fn from(territory_tiles: Vec<Vec<Hex>>) -> Self {
+        Self::new(territory_tiles)
+    }
+}
+
+impl From<HexMap> for Vec<Vec<Hex>> {
+    fn from(map: HexMap) -> Self {
+        map.territory_tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a two hex territory with a one hex territory to its east and another one off on its own
+    fn map() -> HexMap {
+        HexMap::new(vec![
+            vec![Hex::new(0, 0, 0), Hex::new(1, 0, -1)],
+            vec![Hex::new(2, 0, -2)],
+            vec![Hex::new(5, 0, -5)],
+        ])
+    }
+
+    #[test]
+    fn connections_only_join_touching_territories() {
+        assert_eq!(map().connections(), vec![vec![1], vec![0], vec![]]);
+    }
+
+    #[test]
+    fn borders_skip_hexes_in_the_same_territory() {
+        let map = map();
+        assert_eq!(map.borders(Hex::new(0, 0, 0)).len(), 5);
+        assert_eq!(map.borders(Hex::new(2, 0, -2)).len(), 6);
+        assert_eq!(map.territory_at(Hex::new(1, 0, -1)), Some(0));
+        assert_eq!(map.territory_at(Hex::new(3, 0, -3)), None);
+    }
+
+    #[test]
+    fn only_the_territory_tiles_are_saved() {
+        let json = serde_json::to_string(&map()).unwrap();
+        assert_eq!(json, serde_json::to_string(&map().territory_tiles).unwrap());
+
+        let loaded = serde_json::from_str::<HexMap>(&json).unwrap();
+        assert_eq!(loaded.tiles, map().tiles);
+        assert_eq!(loaded.centroids, map().centroids);
+    }
+}
@@ -0,0 +1,9 @@
+pub mod agent;
+pub mod board;
+pub mod expectimax;
+pub mod generator;
+pub mod hex;
+pub mod hex_map;
+pub mod replay;
+pub mod save;
+pub mod scenario;
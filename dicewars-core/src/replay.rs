@@ -0,0 +1,192 @@
+use super::{
+    board::{AttackOutcome, Board, BoardGenSettings},
+    save::{check_board, from_versioned_json, SaveError},
+};
+use serde::{Deserialize, Serialize};
+
+/// bumped whenever the replay format changes in a way that older replays can't be read
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Attack(AttackOutcome),
+    EndTurn,
+}
+
+/// how one player did over a recorded game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    pub attacks: u32,
+    pub attacks_won: u32,
+    /// most territories the player held at once
+    pub most_territories: u32,
+}
+
+/// everything needed to watch a game again. all randomness comes from the board's seeded rng, so
+/// playing the actions back on the starting board rolls the same dice, the recorded rolls are
+/// kept to check that it does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// settings the game was generated with, the seed is always filled in
+    pub settings: BoardGenSettings,
+    pub start: Board,
+    pub actions: Vec<ReplayAction>,
+}
+
+impl Replay {
+    pub fn new(settings: &BoardGenSettings, start: &Board) -> Self {
+        let mut settings = settings.clone();
+        settings.seed = Some(start.seed);
+        Self {
+            version: REPLAY_VERSION,
+            settings,
+            start: start.clone(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn record_attack(&mut self, outcome: &AttackOutcome) {
+        self.actions.push(ReplayAction::Attack(outcome.clone()));
+    }
+
+    pub fn record_end_turn(&mut self) {
+        self.actions.push(ReplayAction::EndTurn);
+    }
+
+    /// the board before the first action followed by the board after each action
+    pub fn states(&self) -> Result<Vec<Board>, SaveError> {
+        let mut board = self.start.clone();
+        let mut states = vec![board.clone()];
+        for (step, action) in self.actions.iter().enumerate() {
+            match action {
+                ReplayAction::Attack(recorded) => {
+                    let outcome = board
+                        .try_attack(recorded.attacker, recorded.defender)
+                        .map_err(|_| SaveError::Desync(step))?;
+                    if outcome.attacker_rolls != recorded.attacker_rolls
+                        || outcome.defender_rolls != recorded.defender_rolls
+                    {
+                        return Err(SaveError::Desync(step));
+                    }
+                }
+                ReplayAction::EndTurn => board.finish_turn(),
+            }
+            states.push(board.clone());
+        }
+        Ok(states)
+    }
+
+    /// stats for every player, found by playing the game back
+    pub fn player_stats(&self) -> Result<Vec<PlayerStats>, SaveError> {
+        let states = self.states()?;
+        let mut stats = vec![PlayerStats::default(); self.start.stockpiles.len()];
+        for state in states.iter() {
+            let territories = state.count_territories();
+            for (player, stats) in stats.iter_mut().enumerate() {
+                stats.most_territories = stats.most_territories.max(territories[player]);
+            }
+        }
+        for (before, action) in states.iter().zip(self.actions.iter()) {
+            if let ReplayAction::Attack(outcome) = action {
+                let stats = &mut stats[before.owner(outcome.attacker)];
+                stats.attacks += 1;
+                if outcome.win {
+                    stats.attacks_won += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replay should always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        let replay = from_versioned_json::<Self>(json, REPLAY_VERSION)?;
+        check_board(&replay.start)?;
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// records a game where every player attacks with the first move they have
+    fn recorded(steps: usize) -> (Replay, Board) {
+        let settings = BoardGenSettings {
+            seed: Some(11),
+            ..Default::default()
+        };
+        let mut board = Board::generate(&settings).unwrap();
+        let mut replay = Replay::new(&settings, &board);
+        for _ in 0..steps {
+            match board.all_moves().first() {
+                Some((first, second)) => {
+                    let outcome = board.try_attack(*first, *second).unwrap();
+                    replay.record_attack(&outcome);
+                }
+                None => {
+                    board.finish_turn();
+                    replay.record_end_turn();
+                }
+            }
+        }
+        (replay, board)
+    }
+
+    fn json(board: &Board) -> String {
+        serde_json::to_string(board).unwrap()
+    }
+
+    #[test]
+    fn states_play_the_game_back() {
+        let (replay, board) = recorded(100);
+        let states = replay.states().unwrap();
+        assert_eq!(states.len(), replay.actions.len() + 1);
+        assert_eq!(json(&states[0]), json(&replay.start));
+        assert_eq!(json(states.last().unwrap()), json(&board));
+
+        let loaded = Replay::from_json(&replay.to_json()).unwrap();
+        assert_eq!(json(loaded.states().unwrap().last().unwrap()), json(&board));
+    }
+
+    #[test]
+    fn changed_rolls_are_a_desync() {
+        let (mut replay, _) = recorded(20);
+        let step = replay
+            .actions
+            .iter()
+            .position(|action| matches!(action, ReplayAction::Attack(_)))
+            .unwrap();
+        if let ReplayAction::Attack(outcome) = &mut replay.actions[step] {
+            outcome.attacker_rolls[0] = outcome.attacker_rolls[0] % 6 + 1;
+        }
+        assert!(matches!(replay.states(), Err(SaveError::Desync(s)) if s == step));
+    }
+
+    #[test]
+    fn impossible_attacks_are_a_desync() {
+        let (mut replay, _) = recorded(20);
+        replay.actions.insert(0, ReplayAction::EndTurn);
+        assert!(matches!(replay.states(), Err(SaveError::Desync(_))));
+    }
+
+    #[test]
+    fn player_stats_count_every_attack() {
+        let (replay, _) = recorded(100);
+        let attacks = replay
+            .actions
+            .iter()
+            .filter(|action| matches!(action, ReplayAction::Attack(_)))
+            .count();
+        let stats = replay.player_stats().unwrap();
+        assert_eq!(
+            stats.iter().map(|s| s.attacks).sum::<u32>() as usize,
+            attacks
+        );
+        assert!(stats.iter().all(|s| s.attacks_won <= s.attacks));
+    }
+}
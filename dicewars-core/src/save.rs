@@ -0,0 +1,259 @@
+use super::{
+    agent::Seat,
+    board::{Board, GameOutcome, GenerateError, MAX_PLAYERS},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// bumped whenever the save format changes in a way that older saves can't be read
+pub const SAVE_VERSION: u32 = 2;
+
+/// a game in progress, stored as json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub board: Board,
+    /// who plays each player, empty to use the game's own seats
+    #[serde(default)]
+    pub seats: Vec<Seat>,
+    /// the board the game started from so a rematch can start over, `None` in older saves
+    #[serde(default)]
+    pub start: Option<Board>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Parse(serde_json::Error),
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// a replay action at this step couldn't be played back the way it was recorded
+    Desync(usize),
+    /// the board has something out of range that would crash the game, files made by the game
+    /// never do this but hand edited or corrupted ones can
+    InvalidBoard(&'static str),
+    /// a scenario file that can't be played
+    InvalidScenario(GenerateError),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Parse(error) => write!(f, "couldn't read file: {}", error),
+            SaveError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "file is version {} but only version {} is supported",
+                found, supported
+            ),
+            SaveError::Desync(step) => {
+                write!(f, "replay doesn't match the game rules at step {}", step)
+            }
+            SaveError::InvalidBoard(reason) => write!(f, "the saved board is broken: {}", reason),
+            SaveError::InvalidScenario(error) => write!(f, "the scenario is broken: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self {
+        SaveError::Parse(error)
+    }
+}
+
+impl SaveGame {
+    pub fn new(board: &Board, seats: &[Seat], start: &Board) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            board: board.clone(),
+            seats: seats.to_vec(),
+            start: Some(start.clone()),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("save game should always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        let save = from_versioned_json::<Self>(json, SAVE_VERSION)?;
+        check_board(&save.board)?;
+        if let Some(start) = &save.start {
+            check_board(start)?;
+        }
+        Ok(save)
+    }
+}
+
+/// parses json with a top level `version` field, checking the version first so old files give a
+/// useful error instead of a missing field
+pub(crate) fn from_versioned_json<T: DeserializeOwned>(
+    json: &str,
+    supported: u32,
+) -> Result<T, SaveError> {
+    #[derive(Deserialize)]
+    struct Header {
+        version: u32,
+    }
+    let header = serde_json::from_str::<Header>(json)?;
+    if header.version != supported {
+        return Err(SaveError::UnsupportedVersion {
+            found: header.version,
+            supported,
+        });
+    }
+
+    Ok(serde_json::from_str(json)?)
+}
+
+/// checks everything that's used as an index, so a bad file gives an error when it's loaded
+/// instead of a panic when it's played or drawn
+pub(crate) fn check_board(board: &Board) -> Result<(), SaveError> {
+    let players = board.stockpiles.len();
+    let territories = board.territories.len();
+    let invalid = |reason| Err(SaveError::InvalidBoard(reason));
+
+    if players == 0 || players > MAX_PLAYERS {
+        return invalid("wrong number of players");
+    }
+    if board.player_order.iter().any(|player| *player >= players) {
+        return invalid("a player in the turn order isn't in the game");
+    }
+    if board.turn >= board.player_order.len() {
+        return invalid("the turn is out of range");
+    }
+    if board.map.territory_tiles.len() != territories {
+        return invalid("the map doesn't have the same number of territories as the board");
+    }
+    if !board.rules.is_playable() {
+        return invalid("the rules need dice with at least one face and room for one die");
+    }
+    for (index, territory) in board.territories.iter().enumerate() {
+        if territory.owner >= players {
+            return invalid("a territory is owned by a player who isn't in the game");
+        }
+        if territory.dice == 0 {
+            return invalid("a territory has no dice");
+        }
+        if board
+            .connections(index)
+            .iter()
+            .any(|neighbor| *neighbor >= territories)
+        {
+            return invalid("a territory is connected to one that doesn't exist");
+        }
+    }
+    let condition_players = board
+        .win_conditions
+        .iter()
+        .filter_map(|condition| condition.player);
+    let winner = match board.outcome() {
+        GameOutcome::Won { player, .. } => Some(player),
+        GameOutcome::InProgress => None,
+    };
+    if condition_players
+        .chain(winner)
+        .any(|player| player >= players)
+    {
+        return invalid("a win condition or the winner is a player who isn't in the game");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agent::BotKind, board::BoardGenSettings};
+
+    fn board() -> Board {
+        Board::generate(&BoardGenSettings {
+            seed: Some(7),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// saves `board` and loads it again after `edit` has changed the json
+    fn reload(board: &Board, edit: impl Fn(&mut serde_json::Value)) -> Result<SaveGame, SaveError> {
+        let mut json = serde_json::to_value(SaveGame::new(board, &[], board)).unwrap();
+        edit(&mut json);
+        SaveGame::from_json(&json.to_string())
+    }
+
+    #[test]
+    fn save_round_trips() {
+        let start = board();
+        let mut board = start.clone();
+        let moves = board.all_moves();
+        let (first, second) = moves[0];
+        board.try_attack(first, second).unwrap();
+        let seats = [Seat::Human, Seat::Bot(BotKind::Cautious), Seat::Human];
+
+        let loaded = SaveGame::from_json(&SaveGame::new(&board, &seats, &start).to_json()).unwrap();
+        assert_eq!(loaded.seats, seats);
+        assert_eq!(
+            serde_json::to_string(&loaded.start).unwrap(),
+            serde_json::to_string(&Some(start)).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&loaded.board).unwrap(),
+            serde_json::to_string(&board).unwrap()
+        );
+        assert_eq!(loaded.board.map.tiles, board.map.tiles);
+
+        // the rng is saved too so the game carries on with the same rolls
+        let mut loaded = loaded.board;
+        board.finish_turn();
+        loaded.finish_turn();
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&board).unwrap()
+        );
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let result = reload(&board(), |json| json["version"] = (SAVE_VERSION + 1).into());
+        assert!(matches!(
+            result,
+            Err(SaveError::UnsupportedVersion { found, supported })
+                if found == SAVE_VERSION + 1 && supported == SAVE_VERSION
+        ));
+        assert!(matches!(
+            SaveGame::from_json("{\"board\": 1}"),
+            Err(SaveError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn saves_without_seats_or_start_still_load() {
+        let save = reload(&board(), |json| {
+            json.as_object_mut().unwrap().remove("seats");
+            json.as_object_mut().unwrap().remove("start");
+        })
+        .unwrap();
+        assert!(save.seats.is_empty());
+        assert!(save.start.is_none());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let edits: [fn(&mut serde_json::Value); 6] = [
+            |json| json["start"]["turn"] = 99.into(),
+            |json| json["board"]["turn"] = 99.into(),
+            |json| json["board"]["territories"][0]["owner"] = 99.into(),
+            |json| json["board"]["territories"][0]["connections"][0] = 999.into(),
+            |json| json["board"]["player_order"][0] = 99.into(),
+            |json| {
+                json["board"]["map"].as_array_mut().unwrap().pop();
+            },
+        ];
+        for edit in edits {
+            assert!(matches!(
+                reload(&board(), edit),
+                Err(SaveError::InvalidBoard(_))
+            ));
+        }
+    }
+}
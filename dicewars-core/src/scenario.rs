@@ -0,0 +1,379 @@
+use super::{
+    agent::Seat,
+    board::{is_connected, Board, GenerateError, Goal, RuleSet, WinCondition, MAX_PLAYERS},
+    hex::Hex,
+    hex_map::HexMap,
+    save::{from_versioned_json, SaveError},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// bumped whenever the scenario format changes in a way that older files can't be read
+pub const SCENARIO_VERSION: u32 = 1;
+
+/// a hand made starting board, stored as json. used for puzzles and tutorials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub version: u32,
+    pub player_count: usize,
+    pub rules: RuleSet,
+    pub territories: Vec<ScenarioTerritory>,
+    /// who plays each player, the game's own seats are used if this is empty
+    #[serde(default)]
+    pub seats: Vec<Seat>,
+    /// ways to win besides being the last player left
+    #[serde(default)]
+    pub win_conditions: Vec<WinCondition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioTerritory {
+    pub tiles: Vec<Hex>,
+    pub owner: usize,
+    pub dice: u32,
+}
+
+impl Scenario {
+    /// empty map with no territories
+    pub fn new(player_count: usize, rules: RuleSet) -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            player_count,
+            rules,
+            territories: Vec::new(),
+            seats: Vec::new(),
+            win_conditions: Vec::new(),
+        }
+    }
+
+    /// the current state of a game as a scenario, stockpiles and turn order aren't kept
+    pub fn from_board(board: &Board) -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            player_count: board.stockpiles.len(),
+            rules: board.rules,
+            territories: board
+                .territories
+                .iter()
+                .zip(board.map.territory_tiles.iter())
+                .map(|(territory, tiles)| ScenarioTerritory {
+                    tiles: tiles.clone(),
+                    owner: territory.owner,
+                    dice: territory.dice,
+                })
+                .collect(),
+            seats: Vec::new(),
+            win_conditions: board.win_conditions.clone(),
+        }
+    }
+
+    pub fn territory_at(&self, hex: Hex) -> Option<usize> {
+        self.territories
+            .iter()
+            .position(|territory| territory.tiles.contains(&hex))
+    }
+
+    /// moves `hex` into `territory`, taking it from whichever territory had it before. returns
+    /// the index of `territory` afterwards since taking the hex can remove an emptied territory
+    pub fn paint(&mut self, hex: Hex, mut territory: usize) -> usize {
+        if territory >= self.territories.len() || self.territory_at(hex) == Some(territory) {
+            return territory;
+        }
+        if let Some(removed) = self.erase(hex) {
+            if removed < territory {
+                territory -= 1;
+            }
+        }
+        self.territories[territory].tiles.push(hex);
+        territory
+    }
+
+    /// removes `hex` from the map. territories left without any hexes are removed too, returns
+    /// the index of the removed territory if that happened
+    pub fn erase(&mut self, hex: Hex) -> Option<usize> {
+        let index = self.territory_at(hex)?;
+        self.territories[index].tiles.retain(|tile| *tile != hex);
+        if self.territories[index].tiles.is_empty() {
+            self.territories.remove(index);
+            return Some(index);
+        }
+        None
+    }
+
+    /// checks the scenario can be played, the editor lets maps be in any state while they're
+    /// being made
+    pub fn validate(&self) -> Result<(), GenerateError> {
+        if self.player_count < 2 || self.player_count > MAX_PLAYERS {
+            return Err(GenerateError::PlayerCount(self.player_count));
+        }
+        if !self.rules.is_playable() {
+            return Err(GenerateError::Rules {
+                die_faces: self.rules.die_faces,
+                max_dice: self.rules.max_dice,
+            });
+        }
+
+        let mut seen = HashSet::new();
+        for (index, territory) in self.territories.iter().enumerate() {
+            if territory.tiles.is_empty() {
+                return Err(GenerateError::EmptyTerritory(index));
+            }
+            if let Some(hex) = territory.tiles.iter().find(|tile| !seen.insert(**tile)) {
+                return Err(GenerateError::Overlap(*hex));
+            }
+            if territory.owner >= self.player_count {
+                return Err(GenerateError::Owner {
+                    territory: index,
+                    owner: territory.owner,
+                });
+            }
+            if territory.dice == 0 || territory.dice > self.rules.max_dice {
+                return Err(GenerateError::TerritoryDice {
+                    territory: index,
+                    dice: territory.dice,
+                });
+            }
+        }
+
+        if !self.seats.is_empty() && self.seats.len() != self.player_count {
+            return Err(GenerateError::Seats {
+                seats: self.seats.len(),
+                players: self.player_count,
+            });
+        }
+        for condition in self.win_conditions.iter() {
+            match condition.player {
+                Some(player) if player >= self.player_count => {
+                    return Err(GenerateError::ConditionPlayer(player));
+                }
+                _ => {}
+            }
+            if condition.goal == Goal::HoldTerritories(0) || condition.goal == Goal::Survive(0) {
+                return Err(GenerateError::Goal(condition.goal));
+            }
+        }
+
+        for player in 0..self.player_count {
+            if !self
+                .territories
+                .iter()
+                .any(|territory| territory.owner == player)
+            {
+                return Err(GenerateError::NoTerritories(player));
+            }
+        }
+
+        let map = HexMap::new(
+            self.territories
+                .iter()
+                .map(|territory| territory.tiles.clone())
+                .collect(),
+        );
+        if !is_connected(&map.connections()) {
+            return Err(GenerateError::Disconnected);
+        }
+        Ok(())
+    }
+
+    /// a board ready to play, `seed` is used for the dice rolls
+    pub fn to_board(&self, seed: u64) -> Result<Board, GenerateError> {
+        self.validate()?;
+        Ok(Board::from_scenario(self, seed))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("scenario should always serialize")
+    }
+
+    /// only playable scenarios are loaded, so a hand edited file can't put out of range players
+    /// or owners into the game or the editor
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        let scenario = from_versioned_json::<Self>(json, SCENARIO_VERSION)?;
+        scenario.validate().map_err(SaveError::InvalidScenario)?;
+        Ok(scenario)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// two players with a territory each
+    fn scenario() -> Scenario {
+        let mut scenario = Scenario::new(2, RuleSet::default());
+        scenario.territories = (0..2)
+            .map(|i| ScenarioTerritory {
+                tiles: vec![Hex::new(i, 0, -i)],
+                owner: i as usize,
+                dice: 2,
+            })
+            .collect();
+        scenario
+    }
+
+    #[test]
+    fn scenarios_round_trip() {
+        let json = scenario().to_json();
+        let loaded = Scenario::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+    }
+
+    #[test]
+    fn malformed_scenarios_are_rejected() {
+        let edits: [fn(&mut serde_json::Value); 4] = [
+            |json| json["player_count"] = 0.into(),
+            |json| json["player_count"] = (MAX_PLAYERS + 1).into(),
+            |json| json["territories"][0]["owner"] = 99.into(),
+            |json| {
+                json["win_conditions"] = serde_json::json!([{"player": 99, "goal": "Eliminate"}])
+            },
+        ];
+        for edit in edits {
+            let mut json =
+                serde_json::from_str::<serde_json::Value>(&scenario().to_json()).unwrap();
+            edit(&mut json);
+            assert!(matches!(
+                Scenario::from_json(&json.to_string()),
+                Err(SaveError::InvalidScenario(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rules_that_cant_be_played_are_rejected() {
+        for (die_faces, max_dice) in [(0, 8), (6, 0)] {
+            let mut scenario = scenario();
+            scenario.rules.die_faces = die_faces;
+            scenario.rules.max_dice = max_dice;
+            assert_eq!(
+                scenario.validate(),
+                Err(GenerateError::Rules {
+                    die_faces,
+                    max_dice
+                })
+            );
+            assert!(scenario.to_board(0).is_err());
+        }
+    }
+
+    #[test]
+    fn validate_catches_maps_that_cant_be_played() {
+        assert_eq!(scenario().validate(), Ok(()));
+
+        let with = |edit: fn(&mut Scenario)| {
+            let mut scenario = scenario();
+            edit(&mut scenario);
+            scenario.validate()
+        };
+        assert_eq!(
+            with(|s| s.territories[1].tiles.clear()),
+            Err(GenerateError::EmptyTerritory(1))
+        );
+        assert_eq!(
+            with(|s| s.territories[1].tiles = vec![Hex::ZERO]),
+            Err(GenerateError::Overlap(Hex::ZERO))
+        );
+        assert_eq!(
+            with(|s| s.territories[1].owner = 2),
+            Err(GenerateError::Owner {
+                territory: 1,
+                owner: 2
+            })
+        );
+        assert_eq!(
+            with(|s| s.territories[0].dice = 9),
+            Err(GenerateError::TerritoryDice {
+                territory: 0,
+                dice: 9
+            })
+        );
+        assert_eq!(
+            with(|s| s.territories[1].owner = 0),
+            Err(GenerateError::NoTerritories(1))
+        );
+        assert_eq!(
+            with(|s| s.territories[1].tiles = vec![Hex::new(5, 0, -5)]),
+            Err(GenerateError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn painting_moves_hexes_between_territories() {
+        let mut scenario = scenario();
+        scenario.territories[1].tiles.push(Hex::new(2, 0, -2));
+        assert_eq!(scenario.paint(Hex::new(1, 0, -1), 0), 0);
+        assert_eq!(scenario.territory_at(Hex::new(1, 0, -1)), Some(0));
+
+        // taking the last hex of a territory removes it
+        assert_eq!(scenario.paint(Hex::new(2, 0, -2), 0), 0);
+        assert_eq!(scenario.territories.len(), 1);
+        assert_eq!(scenario.erase(Hex::ZERO), None);
+        assert_eq!(scenario.territories[0].tiles.len(), 2);
+    }
+
+    #[test]
+    fn boards_from_scenarios_keep_the_map() {
+        let board = scenario().to_board(3).unwrap();
+        assert_eq!(board.player_order, vec![0, 1]);
+        assert_eq!(board.connections(0), &[1]);
+        assert_eq!(board.territories[1].dice, 2);
+        assert_eq!(Scenario::from_board(&board).to_json(), scenario().to_json());
+    }
+
+    #[test]
+    fn seats_and_win_conditions_are_checked() {
+        let with = |seats: Vec<Seat>, condition: WinCondition| {
+            let mut scenario = scenario();
+            scenario.seats = seats;
+            scenario.win_conditions = vec![condition];
+            scenario.validate()
+        };
+        let eliminate = WinCondition {
+            player: None,
+            goal: Goal::Eliminate,
+        };
+        assert_eq!(with(vec![Seat::Human; 2], eliminate), Ok(()));
+        assert_eq!(
+            with(vec![Seat::Human], eliminate),
+            Err(GenerateError::Seats {
+                seats: 1,
+                players: 2
+            })
+        );
+        assert_eq!(
+            with(
+                Vec::new(),
+                WinCondition {
+                    player: Some(2),
+                    goal: Goal::Eliminate
+                }
+            ),
+            Err(GenerateError::ConditionPlayer(2))
+        );
+        assert_eq!(
+            with(
+                Vec::new(),
+                WinCondition {
+                    player: Some(1),
+                    goal: Goal::Survive(0)
+                }
+            ),
+            Err(GenerateError::Goal(Goal::Survive(0)))
+        );
+    }
+
+    #[test]
+    fn boards_get_the_scenario_win_conditions() {
+        let mut scenario = scenario();
+        scenario.win_conditions = vec![WinCondition {
+            player: Some(1),
+            goal: Goal::HoldTerritories(2),
+        }];
+        let board = scenario.to_board(0).unwrap();
+        assert_eq!(board.win_conditions, scenario.win_conditions);
+        assert_eq!(
+            Scenario::from_board(&board).win_conditions,
+            scenario.win_conditions
+        );
+    }
+}
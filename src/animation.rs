@@ -0,0 +1,251 @@
+use super::{board_renderer::SCALE, editor::MapEditor, replay::ReplayPlayback};
+use bevy::{prelude::*, window::RequestRedraw};
+use dicewars_core::board::{AttackOutcome, Board};
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AttackAnimation {
+            playing: None,
+            skip: false,
+            speed: 1.0,
+        })
+        .add_system(animation_system)
+        .add_system(roll_text_system);
+    }
+}
+
+// seconds each phase takes at normal speed
+const HIGHLIGHT_TIME: f32 = 0.25;
+const ROLL_TIME: f32 = 0.8;
+const CAPTURE_TIME: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackPhase {
+    /// attacker and defender light up
+    Highlight,
+    /// both sides' rolls are shown over the board
+    Roll,
+    /// the result is applied and the dice move into a captured territory
+    Capture,
+}
+
+struct PlayingAttack {
+    /// board as it was before the attack
+    before: Board,
+    outcome: AttackOutcome,
+    elapsed: f32,
+}
+
+/// shows the last attack step by step, nobody can move until it's done
+pub struct AttackAnimation {
+    playing: Option<PlayingAttack>,
+    /// apply attacks straight away
+    pub skip: bool,
+    pub speed: f32,
+}
+
+impl AttackAnimation {
+    /// starts showing an attack that has already been made on the board
+    pub fn play(&mut self, before: &Board, outcome: &AttackOutcome) {
+        if self.skip {
+            return;
+        }
+        self.playing = Some(PlayingAttack {
+            before: before.clone(),
+            outcome: outcome.clone(),
+            elapsed: 0.0,
+        });
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.playing = None;
+    }
+
+    pub fn outcome(&self) -> Option<&AttackOutcome> {
+        self.playing.as_ref().map(|playing| &playing.outcome)
+    }
+
+    pub fn phase(&self) -> Option<AttackPhase> {
+        let elapsed = self.playing.as_ref()?.elapsed;
+        Some(if elapsed < HIGHLIGHT_TIME {
+            AttackPhase::Highlight
+        } else if elapsed < HIGHLIGHT_TIME + ROLL_TIME {
+            AttackPhase::Roll
+        } else {
+            AttackPhase::Capture
+        })
+    }
+
+    /// how far through the capture phase the animation is, from 0 to 1
+    pub fn capture_progress(&self) -> f32 {
+        match &self.playing {
+            Some(playing) => {
+                ((playing.elapsed - HIGHLIGHT_TIME - ROLL_TIME) / CAPTURE_TIME).clamp(0.0, 1.0)
+            }
+            None => 1.0,
+        }
+    }
+
+    /// the board to draw, the attack isn't shown on it until the capture phase
+    pub fn board<'a>(&'a self, live: &'a Board) -> &'a Board {
+        match (&self.playing, self.phase()) {
+            (Some(playing), Some(AttackPhase::Highlight | AttackPhase::Roll)) => &playing.before,
+            _ => live,
+        }
+    }
+}
+
+fn animation_system(
+    mut animation: ResMut<AttackAnimation>,
+    time: Res<Time>,
+    playback: Res<ReplayPlayback>,
+    editor: Res<MapEditor>,
+    mut redraw_events: EventWriter<RequestRedraw>,
+) {
+    // the board being animated has been put aside
+    if animation.is_playing() && (playback.is_active() || editor.is_active() || animation.skip) {
+        animation.clear();
+    }
+
+    // only touch the resource while something is playing so the dice aren't redrawn every frame
+    if !animation.is_playing() {
+        return;
+    }
+    // keep updating while the window is in low power mode
+    redraw_events.send(RequestRedraw);
+
+    let speed = animation.speed;
+    if let Some(playing) = animation.playing.as_mut() {
+        playing.elapsed += time.delta_seconds() * speed;
+        if playing.elapsed >= HIGHLIGHT_TIME + ROLL_TIME + CAPTURE_TIME {
+            animation.clear();
+        }
+    }
+}
+
+#[derive(Component)]
+struct RollText;
+
+/// shows each side's dice and total over their territory during the roll phase
+fn roll_text_system(
+    mut commands: Commands,
+    animation: Res<AttackAnimation>,
+    board: Res<Board>,
+    asset_server: Res<AssetServer>,
+    texts: Query<Entity, With<RollText>>,
+) {
+    let rolling = animation.phase() == Some(AttackPhase::Roll);
+    if !rolling {
+        for text in texts.iter() {
+            commands.entity(text).despawn();
+        }
+        return;
+    }
+    if !texts.is_empty() {
+        return;
+    }
+
+    let outcome = animation.outcome().unwrap();
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 24.0,
+        color: Color::BLACK,
+    };
+    let sides = [
+        (
+            outcome.attacker,
+            &outcome.attacker_rolls,
+            outcome.attacker_total,
+        ),
+        (
+            outcome.defender,
+            &outcome.defender_rolls,
+            outcome.defender_total,
+        ),
+    ];
+    for (territory, rolls, total) in sides {
+        let faces = rolls
+            .iter()
+            .map(|roll| roll.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        // above the dice stack
+        let position = Vec2::from(board.map.centroids[territory]) * SCALE + Vec2::Y * 80.0;
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::from_section(format!("{}\n= {}", faces, total), style.clone())
+                    .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_translation(position.extend(999.0)),
+                ..default()
+            })
+            .insert(RollText);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicewars_core::board::BoardGenSettings;
+
+    /// an animation that has just started playing the first attack of a new game
+    fn playing() -> (AttackAnimation, Board) {
+        let mut board = Board::generate(&BoardGenSettings {
+            seed: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        let before = board.clone();
+        let (first, second) = board.all_moves()[0];
+        let outcome = board.try_attack(first, second).unwrap();
+        let mut animation = AttackAnimation {
+            playing: None,
+            skip: false,
+            speed: 1.0,
+        };
+        animation.play(&before, &outcome);
+        (animation, board)
+    }
+
+    fn seek(animation: &mut AttackAnimation, elapsed: f32) {
+        animation.playing.as_mut().unwrap().elapsed = elapsed;
+    }
+
+    #[test]
+    fn the_attack_only_shows_on_the_board_once_it_captures() {
+        let (mut animation, board) = playing();
+        assert_eq!(animation.phase(), Some(AttackPhase::Highlight));
+        assert!(!std::ptr::eq(animation.board(&board), &board));
+
+        seek(&mut animation, HIGHLIGHT_TIME);
+        assert_eq!(animation.phase(), Some(AttackPhase::Roll));
+        assert!(!std::ptr::eq(animation.board(&board), &board));
+        assert_eq!(animation.capture_progress(), 0.0);
+
+        let halfway = HIGHLIGHT_TIME + ROLL_TIME + CAPTURE_TIME / 2.0;
+        seek(&mut animation, halfway);
+        assert_eq!(animation.phase(), Some(AttackPhase::Capture));
+        assert!(std::ptr::eq(animation.board(&board), &board));
+        assert!((animation.capture_progress() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn skipped_attacks_are_never_played() {
+        let (mut animation, board) = playing();
+        let outcome = animation.outcome().unwrap().clone();
+        animation.clear();
+        assert!(!animation.is_playing());
+        assert_eq!(animation.phase(), None);
+        assert_eq!(animation.capture_progress(), 1.0);
+
+        animation.skip = true;
+        animation.play(&board, &outcome);
+        assert!(!animation.is_playing());
+        assert!(std::ptr::eq(animation.board(&board), &board));
+    }
+}
@@ -1,10 +1,23 @@
-use super::board::{Board, BoardGenSettings};
+use super::{
+    animation::{AttackAnimation, AttackPhase},
+    undo::UndoHistory,
+    AttackLog, GameStart,
+};
 use bevy::{
     prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     sprite::MaterialMesh2dBundle,
     ui::{FocusPolicy, Interaction},
 };
 use bevy_mod_picking::*;
+use dicewars_core::{
+    agent::Seat,
+    board::{Board, BoardGenSettings, GenerateError},
+    hex::Hex,
+    hex_map::HexMap,
+    replay::Replay,
+};
+use std::sync::Arc;
 
 pub struct BoardPlugin;
 
@@ -12,22 +25,27 @@ impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(bevy_obj::ObjPlugin)
             .add_event::<RegenerateBoardEvent>()
-            .insert_resource(BoardGenSettings {
-                player_count: 3,
-                board_size: 20,
-            })
+            .add_event::<LoadBoardEvent>()
+            .insert_resource(BoardGenSettings::default())
+            .insert_resource(GenerateStatus { error: None })
             .add_startup_system(setup)
             .add_stage_after(CoreStage::Update, "Post", SystemStage::parallel())
-            .add_system_to_stage("Post", update_board);
+            .add_system_to_stage("Post", update_board.label(UpdateBoard))
+            .add_system_to_stage("Post", update_palette.before(UpdateBoard));
     }
 }
 
+/// runs in the "Post" stage, after it the board on screen matches the `Board` resource
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UpdateBoard;
+
 // this squishes the board verticly to make it look like it has perspective
 pub const SCALE: Vec2 = Vec2::new(12.0, 9.0);
 
 #[derive(Component)]
 pub struct Tile {
     pub index: usize,
+    pub hex: Hex,
 }
 
 #[derive(Component)]
@@ -36,67 +54,187 @@ struct Edge;
 #[derive(Component)]
 struct Dice;
 
+/// parent of a territory's dice, remembers what they were spawned for
+#[derive(Component)]
+struct DiceStack {
+    territory: usize,
+    owner: usize,
+    colour: Color,
+    faces: Vec<u32>,
+    height: u32,
+}
+
 pub struct BoardRenderData {
-    positions: Vec<Vec2>,
+    /// map the tile entities were spawned from
+    map: Arc<HexMap>,
+    /// player colours from the current palette
     pub colours: Vec<Color>,
+    pub palette: Palette,
+    /// draw a different pattern on each player's territories so they don't rely on colour
+    pub patterns: bool,
+    pub names: Vec<String>,
     pub selected: Option<usize>,
     pub hovered: Option<usize>,
     pub attackable: Vec<usize>,
-    // quad, hexagon, edge
-    meshes: (Handle<Mesh>, Handle<Mesh>, Handle<Mesh>),
-    // territory normal, territory hovered, territory attackable, dice material
+    /// dice in each column of a stack, taller stacks carry on in another column behind
+    pub stack_height: u32,
+    // hexagon, edge
+    meshes: (Handle<Mesh>, Handle<Mesh>),
+    // territory normal, territory hovered, territory attackable
     materials: Vec<(
         Handle<ColorMaterial>,
         Handle<ColorMaterial>,
         Handle<ColorMaterial>,
-        Handle<ColorMaterial>,
     )>,
     edge_material: Handle<ColorMaterial>,
     selected_material: Handle<ColorMaterial>,
     selected_material_hover: Handle<ColorMaterial>,
+    /// one die for each face from one to six, tinted with the owner's colour
+    dice_atlas: Handle<TextureAtlas>,
+    /// numbers on dice with more faces than the atlas has
+    dice_font: Handle<Font>,
+    pattern_textures: Vec<Handle<Image>>,
+    /// faces each territory's dice last rolled, the rest of a stack gets made up faces
+    faces: Vec<Vec<u32>>,
+    /// attacks from the log that have been put on the dice
+    attacks_shown: usize,
+    /// where this board's attacks start in the log
+    first_attack: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Default,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::HighContrast,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Protanopia => "Protanopia",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    /// one colour for each player
+    pub fn colours(&self) -> Vec<Color> {
+        let colours = match self {
+            Palette::Default => [
+                (0, 147, 2),
+                (255, 255, 3),
+                (180, 126, 254),
+                (255, 127, 255),
+                (179, 255, 4),
+                (255, 127, 1),
+                (255, 88, 89),
+                (178, 255, 254),
+            ],
+            // Okabe and Ito's colour-blind safe set, with dark grey instead of black so the dice
+            // still show up. light grey would look like a selected territory
+            Palette::Deuteranopia => [
+                (0, 114, 178),
+                (230, 159, 0),
+                (86, 180, 233),
+                (0, 158, 115),
+                (240, 228, 66),
+                (213, 94, 0),
+                (204, 121, 167),
+                (80, 80, 80),
+            ],
+            // Paul Tol's muted set, chosen to stay apart without red cones as well
+            Palette::Protanopia => [
+                (204, 102, 119),
+                (51, 34, 136),
+                (221, 204, 119),
+                (17, 119, 51),
+                (136, 204, 238),
+                (136, 34, 85),
+                (68, 170, 153),
+                (153, 153, 51),
+            ],
+            Palette::HighContrast => [
+                (0, 0, 255),
+                (255, 255, 0),
+                (255, 0, 0),
+                (0, 255, 255),
+                (0, 160, 0),
+                (255, 0, 255),
+                (255, 140, 0),
+                (110, 60, 0),
+            ],
+        };
+        colours
+            .iter()
+            .map(|(r, g, b)| Color::rgb_u8(*r, *g, *b))
+            .collect()
+    }
 }
 
 pub struct RegenerateBoardEvent;
 
+/// why the last new game couldn't be generated, the old board is kept when that happens
+pub struct GenerateStatus {
+    pub error: Option<GenerateError>,
+}
+
+/// replaces the current game with a saved one
+pub struct LoadBoardEvent {
+    pub board: Board,
+    /// who plays each player, `None` keeps the seats from the game settings
+    pub seats: Option<Vec<Seat>>,
+    /// board the game started from, `None` when `board` is the start
+    pub start: Option<Board>,
+}
+
 fn setup(
     mut commands: Commands,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut material_assets: ResMut<Assets<ColorMaterial>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
     asset_server: Res<AssetServer>,
     mut regenerate_board_event: EventWriter<RegenerateBoardEvent>,
 ) {
     // spawn in tiles
-    let colours = vec![
-        Color::rgb_u8(0, 147, 2),
-        Color::rgb_u8(255, 255, 3),
-        Color::rgb_u8(180, 126, 254),
-        Color::rgb_u8(255, 127, 255),
-        Color::rgb_u8(179, 255, 4),
-        Color::rgb_u8(255, 127, 1),
-        Color::rgb_u8(255, 88, 89),
-        Color::rgb_u8(178, 255, 254),
-    ];
+    let colours = Palette::Default.colours();
 
+    // the hexagon is built here rather than loaded so it has uvs for the patterns
     let meshes = (
-        mesh_assets.add(Mesh::from(shape::Quad::default())),
-        asset_server.load("hexagon.obj"),
+        mesh_assets.add(Mesh::from(shape::RegularPolygon::new(1.0, 6))),
         asset_server.load("edge.obj"),
     );
 
-    let dice_texture = asset_server.load("dice.png");
     let mut materials = Vec::new();
     for i in 0..8 {
         materials.push((
             material_assets.add(ColorMaterial::from(colours[i])),
             material_assets.add(ColorMaterial::from(colours[i] * 0.8)),
             material_assets.add(ColorMaterial::from(colours[i] * 0.9)),
-            material_assets.add(ColorMaterial {
-                color: colours[i],
-                texture: Some(dice_texture.clone()),
-            }),
         ));
     }
 
+    let pattern_textures = (0..8)
+        .map(|player| images.add(pattern_image(player)))
+        .collect();
+
+    let dice_atlas = texture_atlases.add(TextureAtlas::from_grid(
+        asset_server.load("dice_atlas.png"),
+        Vec2::new(270.0, 270.0),
+        6,
+        1,
+    ));
+
     let edge_material = material_assets.add(ColorMaterial::from(Color::rgb_u8(0, 0, 0)));
     let selected_material = material_assets.add(ColorMaterial::from(Color::rgb_u8(240, 240, 240)));
     let selected_material_hover =
@@ -105,22 +243,29 @@ fn setup(
     regenerate_board_event.send(RegenerateBoardEvent);
 
     // add empty board so it doesn't crash
-    commands.insert_resource(Board {
-        turn: 0,
-        player_order: Vec::new(),
-        territories: Vec::new(),
-    });
+    commands.insert_resource(Board::default());
+    commands.insert_resource(Replay::new(&BoardGenSettings::default(), &Board::default()));
     commands.insert_resource(BoardRenderData {
-        positions: Vec::new(),
+        map: Arc::default(),
         colours,
+        palette: Palette::Default,
+        patterns: false,
+        names: (0..8).map(|player| format!("Player {}", player)).collect(),
         selected: None,
         hovered: None,
         attackable: Vec::new(),
+        stack_height: 4,
         meshes,
         materials,
         edge_material,
         selected_material,
         selected_material_hover,
+        dice_atlas,
+        dice_font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        pattern_textures,
+        faces: Vec::new(),
+        attacks_shown: 0,
+        first_attack: 0,
     });
 }
 
@@ -128,81 +273,53 @@ fn update_board(
     mut commands: Commands,
     mut board: ResMut<Board>,
     mut board_render_data: ResMut<BoardRenderData>,
-    dice_query: Query<Entity, With<Dice>>,
+    mut stack_query: Query<(Entity, &mut DiceStack, &mut Transform)>,
     mut tile_query: Query<(&Tile, &mut Handle<ColorMaterial>)>,
     tile_entity_query: Query<Entity, With<Tile>>,
     edge_entity_query: Query<Entity, With<Edge>>,
     mut regenerate_board_event: EventReader<RegenerateBoardEvent>,
+    mut load_board_event: EventReader<LoadBoardEvent>,
     board_gen_settings: Res<BoardGenSettings>,
+    // grouped to stay within the number of parameters a system can take
+    (mut replay, mut history): (ResMut<Replay>, ResMut<UndoHistory>),
+    mut generate_status: ResMut<GenerateStatus>,
+    mut animation: ResMut<AttackAnimation>,
+    attack_log: Res<AttackLog>,
+    mut game_start: ResMut<GameStart>,
+    mut drawn_layout: Local<(u32, Vec<Color>)>,
 ) {
-    // update material handles
-    for (tile, mut material) in tile_query.iter_mut() {
-        if tile.index == board_render_data.selected.unwrap_or(usize::MAX) {
-            if tile.index == board_render_data.hovered.unwrap_or(usize::MAX) {
-                *material = board_render_data.selected_material_hover.clone();
-            } else {
-                *material = board_render_data.selected_material.clone();
-            }
-        } else {
-            if tile.index == board_render_data.hovered.unwrap_or(usize::MAX) {
-                let owner = board.territories[tile.index].owner;
-                *material = board_render_data.materials[owner].1.clone();
-            } else {
-                if board_render_data.attackable.contains(&tile.index) {
-                    let owner = board.territories[tile.index].owner;
-                    *material = board_render_data.materials[owner].2.clone();
-                } else {
-                    let owner = board.territories[tile.index].owner;
-                    *material = board_render_data.materials[owner].0.clone();
-                }
+    // generate or load new board
+    let mut new_boards = Vec::new();
+    for _ in regenerate_board_event.iter() {
+        match Board::generate(&board_gen_settings) {
+            Ok(new_board) => {
+                new_boards.push((new_board, None));
+                generate_status.error = None;
             }
+            Err(error) => generate_status.error = Some(error),
         }
     }
-
-    // update dice
-    for dice in dice_query.iter() {
-        commands.entity(dice).despawn();
+    for event in load_board_event.iter() {
+        new_boards.push((event.board.clone(), event.start.clone()));
     }
-
-    let dice_size = 16.0;
-    for i in 0..board.territories.len() {
-        let dice_count = board.territories[i].dice;
-        let pos = board_render_data.positions[i];
-        let owner = board.territories[i].owner;
-
-        for j in 0..dice_count {
-            let loop_height = 4;
-            let offset = Vec3::new(
-                -dice_size,
-                dice_size / 2.0 - loop_height as f32 * dice_size,
-                -(loop_height as f32 + 1.0) / 1000.0,
-            );
-
-            let mut dice_pos = Vec3::Y * j as f32 * dice_size
-                + pos.extend((loop_height + j) as f32 / 1000.0 + 1.0);
-            if j >= loop_height {
-                dice_pos += offset;
-            }
-
-            dice_pos.z += -pos.y + 500.0;
-
-            let transform = Transform::default()
-                .with_translation(dice_pos)
-                .with_scale(Vec3::splat(dice_size * 3.0));
-
-            commands
-                .spawn_bundle(MaterialMesh2dBundle {
-                    transform,
-                    mesh: board_render_data.meshes.0.clone().into(),
-                    material: board_render_data.materials[owner].3.clone(),
-                    ..default()
-                })
-                .insert(Dice);
-        }
+    if let Some((new_board, start)) = new_boards.pop() {
+        *replay = Replay::new(&board_gen_settings, &new_board);
+        // undo history points into the old replay
+        history.reset(0);
+        game_start.board = start.unwrap_or_else(|| new_board.clone());
+        // rolls from the last game don't belong on the new one, even if the map is the same
+        board_render_data.faces = vec![Vec::new(); new_board.territories.len()];
+        board_render_data.attacks_shown = attack_log.attacks.len();
+        board_render_data.first_attack = attack_log.attacks.len();
+        *board = new_board;
+        animation.clear();
     }
 
-    // generate new board
-    for _ in regenerate_board_event.iter() {
+    // respawn tiles when the board is replaced with one that has a different map
+    let respawn = board.is_changed()
+        && !Arc::ptr_eq(&board.map, &board_render_data.map)
+        && board.map.territory_tiles != board_render_data.map.territory_tiles;
+    if respawn {
         // delete old board
         for tile in tile_entity_query.iter() {
             commands.entity(tile).despawn();
@@ -210,15 +327,16 @@ fn update_board(
         for edge in edge_entity_query.iter() {
             commands.entity(edge).despawn();
         }
+        for (stack, _, _) in stack_query.iter() {
+            commands.entity(stack).despawn_recursive();
+        }
 
-        // generate new board
-        let (new_board, tiles, positions) = Board::generate(&board_gen_settings);
-        for (transform, tile, edges) in tiles {
-            let owner = new_board.territories[tile.index].owner;
+        for (transform, tile, edges) in tile_transforms(&board.map) {
+            let owner = board.territories[tile.index].owner;
             commands
                 .spawn_bundle(MaterialMesh2dBundle {
                     transform,
-                    mesh: board_render_data.meshes.1.clone().into(),
+                    mesh: board_render_data.meshes.0.clone().into(),
                     material: board_render_data.materials[owner].0.clone(),
                     ..default()
                 })
@@ -234,7 +352,7 @@ fn update_board(
                         parent
                             .spawn_bundle(MaterialMesh2dBundle {
                                 transform,
-                                mesh: board_render_data.meshes.2.clone().into(),
+                                mesh: board_render_data.meshes.1.clone().into(),
                                 material: board_render_data.edge_material.clone(),
                                 ..default()
                             })
@@ -243,7 +361,395 @@ fn update_board(
                 });
         }
 
-        board_render_data.positions = positions;
-        *board = new_board;
+        board_render_data.faces = vec![Vec::new(); board.territories.len()];
+        board_render_data.attacks_shown = attack_log.attacks.len();
+        board_render_data.first_attack = attack_log.attacks.len();
+        for (territory, state) in board.territories.iter().enumerate() {
+            let stack = DiceStack {
+                territory,
+                owner: state.owner,
+                colour: board_render_data.colours[state.owner],
+                faces: stack_faces(&[], territory, state.dice, board.rules.die_faces),
+                height: board_render_data.stack_height,
+            };
+            commands
+                .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+                    stack_translation(&board.map, territory),
+                )))
+                .with_children(|parent| spawn_dice(parent, &stack, &board_render_data))
+                .insert(stack);
+        }
+
+        board_render_data.map = board.map.clone();
+        board_render_data.selected = None;
+        board_render_data.hovered = None;
+        board_render_data.attackable = Vec::new();
+    }
+
+    // an attack being animated isn't shown until its capture phase
+    let shown = animation.board(&board);
+    let fighting = match (animation.phase(), animation.outcome()) {
+        (Some(AttackPhase::Highlight | AttackPhase::Roll), Some(outcome)) => {
+            Some((outcome.attacker, outcome.defender))
+        }
+        _ => None,
+    };
+
+    // update material handles, old tiles are still around until the end of the stage
+    for (tile, mut material) in tile_query.iter_mut().filter(|_| !respawn) {
+        if let Some((attacker, defender)) = fighting {
+            let owner = shown.territories[tile.index].owner;
+            if tile.index == attacker {
+                *material = board_render_data.selected_material.clone();
+                continue;
+            }
+            if tile.index == defender {
+                *material = board_render_data.materials[owner].1.clone();
+                continue;
+            }
+        }
+
+        if tile.index == board_render_data.selected.unwrap_or(usize::MAX) {
+            if tile.index == board_render_data.hovered.unwrap_or(usize::MAX) {
+                *material = board_render_data.selected_material_hover.clone();
+            } else {
+                *material = board_render_data.selected_material.clone();
+            }
+        } else {
+            if tile.index == board_render_data.hovered.unwrap_or(usize::MAX) {
+                let owner = shown.territories[tile.index].owner;
+                *material = board_render_data.materials[owner].1.clone();
+            } else {
+                if board_render_data.attackable.contains(&tile.index) {
+                    let owner = shown.territories[tile.index].owner;
+                    *material = board_render_data.materials[owner].2.clone();
+                } else {
+                    let owner = shown.territories[tile.index].owner;
+                    *material = board_render_data.materials[owner].0.clone();
+                }
+            }
+        }
+    }
+
+    // move and restack dice, stacks are only rebuilt when their territory's dice or owner change
+    let layout = (
+        board_render_data.stack_height,
+        board_render_data.colours.clone(),
+    );
+    let layout_changed = *drawn_layout != layout;
+    *drawn_layout = layout;
+    let changed = board.is_changed() || animation.is_changed() || attack_log.is_changed();
+    if respawn || !(changed || layout_changed) {
+        return;
+    }
+
+    // rolls go on the dice once the attack shows on the board
+    let attack_shown = !matches!(
+        animation.phase(),
+        Some(AttackPhase::Highlight | AttackPhase::Roll)
+    );
+    if attack_shown {
+        // undone attacks leave their rolls on the dice, so put the remaining ones back on from the
+        // start of the board
+        if attack_log.attacks.len() < board_render_data.attacks_shown {
+            board_render_data.faces = vec![Vec::new(); board.territories.len()];
+            board_render_data.attacks_shown = board_render_data.first_attack;
+        }
+        let new_attacks = attack_log.attacks.get(board_render_data.attacks_shown..);
+        for attack in new_attacks.unwrap_or_default() {
+            // the attacker keeps one die and the rest move in if the attack won
+            let kept = (attack.attacker_dice as usize).min(attack.attacker_rolls.len());
+            board_render_data.faces[attack.attacker] = attack.attacker_rolls[..kept].to_vec();
+            board_render_data.faces[attack.defender] = if attack.win {
+                attack.attacker_rolls[kept..].to_vec()
+            } else {
+                attack.defender_rolls.clone()
+            };
+        }
+        board_render_data.attacks_shown = attack_log.attacks.len();
+    }
+
+    // dice that won a territory slide over from the attacker
+    let moving = match (animation.phase(), animation.outcome()) {
+        (Some(AttackPhase::Capture), Some(outcome)) if outcome.win => {
+            let from = Vec2::from(shown.map.centroids[outcome.attacker]) * SCALE;
+            let to = Vec2::from(shown.map.centroids[outcome.defender]) * SCALE;
+            Some((
+                outcome.defender,
+                (from - to) * (1.0 - animation.capture_progress()),
+            ))
+        }
+        _ => None,
+    };
+
+    for (entity, mut stack, mut transform) in stack_query.iter_mut() {
+        let territory = &shown.territories[stack.territory];
+        let faces = stack_faces(
+            &board_render_data.faces[stack.territory],
+            stack.territory,
+            territory.dice,
+            shown.rules.die_faces,
+        );
+        let colour = board_render_data.colours[territory.owner];
+        if stack.faces != faces
+            || stack.owner != territory.owner
+            || stack.colour != colour
+            || stack.height != board_render_data.stack_height
+        {
+            stack.faces = faces;
+            stack.owner = territory.owner;
+            stack.colour = colour;
+            stack.height = board_render_data.stack_height;
+            let mut stack_commands = commands.entity(entity);
+            stack_commands.despawn_descendants();
+            stack_commands.with_children(|parent| spawn_dice(parent, &stack, &board_render_data));
+        }
+
+        let mut translation = stack_translation(&shown.map, stack.territory);
+        if let Some((territory, offset)) = moving {
+            if territory == stack.territory {
+                translation += offset.extend(0.0);
+            }
+        }
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+/// where a territory's dice stack sits, lower stacks are drawn in front
+fn stack_translation(map: &HexMap, territory: usize) -> Vec3 {
+    let pos = Vec2::from(map.centroids[territory]) * SCALE;
+    pos.extend(-pos.y + 501.0)
+}
+
+/// faces to show on a stack of `dice`, dice that haven't been rolled yet get made up ones that
+/// could have been rolled with `die_faces`
+fn stack_faces(rolled: &[u32], territory: usize, dice: u32, die_faces: u32) -> Vec<u32> {
+    let die_faces = die_faces.max(1) as usize;
+    (0..dice as usize)
+        .map(|j| match rolled.get(j) {
+            Some(face) => *face,
+            None => ((territory * 5 + j * 3) % die_faces + 1) as u32,
+        })
+        .collect()
+}
+
+fn spawn_dice(parent: &mut ChildBuilder, stack: &DiceStack, board_render_data: &BoardRenderData) {
+    let dice_size = 16.0;
+    let height = stack.height.max(1);
+    for (j, face) in stack.faces.iter().enumerate() {
+        // full columns carry on half a die higher, one die to the left and behind
+        let (column, row) = (j as u32 / height, j as u32 % height);
+        let dice_pos = Vec3::new(
+            -(column as f32) * dice_size,
+            row as f32 * dice_size + column as f32 * dice_size / 2.0,
+            (height + row) as f32 / 1000.0 - column as f32 / 1000.0,
+        );
+
+        // the atlas only has six faces, bigger dice are drawn as a block with the number on it
+        if *face > 6 {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: stack.colour,
+                        custom_size: Some(Vec2::splat(dice_size * 1.25)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(dice_pos),
+                    ..default()
+                })
+                .insert(Dice)
+                .with_children(|parent| {
+                    let style = TextStyle {
+                        font: board_render_data.dice_font.clone(),
+                        font_size: 14.0,
+                        color: Color::BLACK,
+                    };
+                    parent.spawn_bundle(Text2dBundle {
+                        text: Text::from_section(face.to_string(), style)
+                            .with_alignment(TextAlignment::CENTER),
+                        // in front of this die but behind the one above it
+                        transform: Transform::from_xyz(0.0, 0.0, 0.0005),
+                        ..default()
+                    });
+                });
+            continue;
+        }
+
+        parent
+            .spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: face.saturating_sub(1) as usize,
+                    color: stack.colour,
+                    custom_size: Some(Vec2::splat(dice_size * 3.0)),
+                    ..default()
+                },
+                texture_atlas: board_render_data.dice_atlas.clone(),
+                transform: Transform::from_translation(dice_pos),
+                ..default()
+            })
+            .insert(Dice);
+    }
+}
+
+/// recolours the territory materials when the palette or patterns are changed
+fn update_palette(
+    mut board_render_data: ResMut<BoardRenderData>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    mut applied: Local<Option<(Palette, bool)>>,
+) {
+    let wanted = (board_render_data.palette, board_render_data.patterns);
+    if *applied == Some(wanted) {
+        return;
+    }
+    *applied = Some(wanted);
+
+    let colours = board_render_data.palette.colours();
+    for (player, materials) in board_render_data.materials.iter().enumerate() {
+        let texture = board_render_data
+            .patterns
+            .then(|| board_render_data.pattern_textures[player].clone());
+        let shades = [
+            (&materials.0, 1.0),
+            (&materials.1, 0.8),
+            (&materials.2, 0.9),
+        ];
+        for (handle, shade) in shades {
+            if let Some(material) = material_assets.get_mut(handle) {
+                material.color = colours[player] * shade;
+                material.texture = texture.clone();
+            }
+        }
+    }
+    board_render_data.colours = colours;
+}
+
+/// white tile with darker lines or spots that gets tinted by the player's colour, every player
+/// has a different one
+fn pattern_image(player: usize) -> Image {
+    const SIZE: u32 = 32;
+    let mut data = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (u, v) = (x % 8, y % 8);
+            let marked = match player % 8 {
+                0 => false,
+                1 => v < 3,
+                2 => u < 3,
+                3 => (x + y) % 8 < 3,
+                4 => (x + SIZE - y) % 8 < 3,
+                5 => u < 2 || v < 2,
+                6 => (2..6).contains(&u) && (2..6).contains(&v),
+                _ => (x / 8 + y / 8) % 2 == 0,
+            };
+            let value = if marked { 160 } else { 255 };
+            data.extend([value, value, value, 255]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// builds the transforms for every hex and its border edges from the map layout
+fn tile_transforms(map: &HexMap) -> Vec<(Transform, Tile, Vec<Transform>)> {
+    let mut tiles = Vec::new();
+    for (hex, territory_index) in map.tiles.iter() {
+        // add node data
+        let center = Vec2::from(hex.to_grid());
+        let transform =
+            Transform::from_translation((center * SCALE).extend(0.0)).with_scale(SCALE.extend(1.0));
+
+        // add edge data
+        let mut edges = Vec::new();
+        for direction in map.borders(*hex) {
+            let neighbor = Vec2::from((*hex + direction).to_grid());
+            let transform = Transform::from_xyz(0.0, 0.0, 0.5)
+                .looking_at(-Vec3::Z, (neighbor - center).extend(0.0));
+            edges.push(transform);
+        }
+
+        tiles.push((
+            transform,
+            Tile {
+                index: *territory_index,
+                hex: *hex,
+            },
+            edges,
+        ));
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks_sit_on_their_territory_with_lower_ones_in_front() {
+        let board = Board::generate(&BoardGenSettings {
+            seed: Some(4),
+            ..Default::default()
+        })
+        .unwrap();
+        let map = &board.map;
+
+        for territory in 0..map.centroids.len() {
+            let translation = stack_translation(map, territory);
+            let centroid = Vec2::from(map.centroids[territory]) * SCALE;
+            assert_eq!(translation.truncate(), centroid);
+        }
+
+        let lowest = (0..map.centroids.len())
+            .min_by(|a, b| map.centroids[*a].1.total_cmp(&map.centroids[*b].1))
+            .unwrap();
+        let highest = (0..map.centroids.len())
+            .max_by(|a, b| map.centroids[*a].1.total_cmp(&map.centroids[*b].1))
+            .unwrap();
+        assert!(stack_translation(map, lowest).z > stack_translation(map, highest).z);
+    }
+
+    #[test]
+    fn stacks_show_the_last_roll_on_top_of_the_stack() {
+        assert_eq!(stack_faces(&[6, 2, 3], 0, 5, 6)[..3], [6, 2, 3]);
+        // a territory that lost dice only shows as many as it has left
+        assert_eq!(stack_faces(&[6, 2, 3], 0, 1, 6), [6]);
+
+        for die_faces in [2, 6, 12] {
+            for territory in 0..10 {
+                let faces = stack_faces(&[], territory, 8, die_faces);
+                assert_eq!(faces.len(), 8);
+                assert!(faces.iter().all(|face| (1..=die_faces).contains(face)));
+                assert_eq!(faces, stack_faces(&[], territory, 8, die_faces));
+            }
+        }
+    }
+
+    #[test]
+    fn every_palette_tells_all_the_players_apart() {
+        for palette in Palette::ALL {
+            let colours = palette.colours();
+            assert_eq!(colours.len(), 8, "{}", palette.name());
+            for (i, colour) in colours.iter().enumerate() {
+                assert!(!colours[..i].contains(colour), "{}", palette.name());
+            }
+        }
+    }
+
+    #[test]
+    fn every_player_gets_a_different_pattern() {
+        let patterns = (0..8).map(pattern_image).collect::<Vec<_>>();
+        for (i, pattern) in patterns.iter().enumerate() {
+            assert_eq!(pattern.data.len(), 32 * 32 * 4);
+            assert!(patterns[..i].iter().all(|other| other.data != pattern.data));
+        }
     }
 }
@@ -0,0 +1,250 @@
+use super::board_renderer::{LoadBoardEvent, RegenerateBoardEvent, UpdateBoard, SCALE};
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::camera::OrthographicProjection,
+    window::RequestRedraw,
+};
+use bevy_egui::EguiContext;
+use bevy_mod_picking::PickingCameraBundle;
+use dicewars_core::{board::Board, hex_map::HexMap};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup)
+            .add_system(camera_controls)
+            // after the new board has been put in place
+            .add_system_to_stage("Post", fit_new_board.after(UpdateBoard));
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            app.add_system(pinch_zoom);
+        }
+    }
+}
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 10.0;
+/// screen pixels per second when panning with the keyboard
+const PAN_SPEED: f32 = 600.0;
+
+#[derive(Component)]
+pub struct BoardCamera;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert_bundle(PickingCameraBundle::default())
+        .insert(BoardCamera);
+}
+
+/// zooms by `factor` keeping the world point under `screen` (from the window centre) still
+fn zoom_at(
+    transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    factor: f32,
+    screen: Vec2,
+) {
+    let scale = (projection.scale * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    transform.translation += (screen * (projection.scale - scale)).extend(0.0);
+    projection.scale = scale;
+}
+
+/// wheel to zoom, right or middle drag and arrows or wasd to pan, f or home to fit the board
+fn camera_controls(
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+    windows: Res<Windows>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut motion_events: EventReader<MouseMotion>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    board: Res<Board>,
+    mut egui_context: ResMut<EguiContext>,
+    mut redraw_events: EventWriter<RequestRedraw>,
+) {
+    let (mut transform, mut projection) = camera.single_mut();
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+
+    // scrolling and dragging inside the menus shouldn't move the board
+    let ctx = egui_context.ctx_mut();
+    let over_menu = ctx.wants_pointer_input();
+    let typing = ctx.wants_keyboard_input();
+
+    let cursor = window
+        .cursor_position()
+        .map(|cursor| cursor - window_size / 2.0)
+        .unwrap_or(Vec2::ZERO);
+    for event in wheel_events.iter().filter(|_| !over_menu) {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 20.0,
+        };
+        zoom_at(&mut transform, &mut projection, 0.9f32.powf(lines), cursor);
+    }
+
+    let dragging = mouse.any_pressed([MouseButton::Right, MouseButton::Middle]);
+    for event in motion_events.iter().filter(|_| dragging && !over_menu) {
+        // screen y points down
+        transform.translation += Vec3::new(-event.delta.x, event.delta.y, 0.0) * projection.scale;
+    }
+
+    if typing {
+        return;
+    }
+    let mut direction = Vec2::ZERO;
+    if keys.any_pressed([KeyCode::Left, KeyCode::A]) {
+        direction.x -= 1.0;
+    }
+    if keys.any_pressed([KeyCode::Right, KeyCode::D]) {
+        direction.x += 1.0;
+    }
+    if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
+        direction.y += 1.0;
+    }
+    if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
+        direction.y -= 1.0;
+    }
+    if direction != Vec2::ZERO {
+        // keep updating while the window is in low power mode
+        redraw_events.send(RequestRedraw);
+        let step = direction.normalize() * PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += step.extend(0.0);
+    }
+
+    if keys.any_just_pressed([KeyCode::F, KeyCode::Home]) {
+        fit(&mut transform, &mut projection, &board.map, window_size);
+    }
+}
+
+fn fit_new_board(
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+    windows: Res<Windows>,
+    board: Res<Board>,
+    mut regenerate_board_events: EventReader<RegenerateBoardEvent>,
+    mut load_board_events: EventReader<LoadBoardEvent>,
+) {
+    let new_board =
+        regenerate_board_events.iter().count() > 0 || load_board_events.iter().count() > 0;
+    if let (true, Some(window)) = (new_board, windows.get_primary()) {
+        let (mut transform, mut projection) = camera.single_mut();
+        let window_size = Vec2::new(window.width(), window.height());
+        fit(&mut transform, &mut projection, &board.map, window_size);
+    }
+}
+
+/// centres the camera on the map and zooms so all of it is in view
+fn fit(
+    transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    map: &HexMap,
+    window_size: Vec2,
+) {
+    if map.tiles.is_empty() || window_size.min_element() <= 0.0 {
+        return;
+    }
+
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for hex in map.tiles.keys() {
+        let center = Vec2::from(hex.to_grid()) * SCALE;
+        min = min.min(center);
+        max = max.max(center);
+    }
+    // room for the edge hexes and the dice stacked on top of them
+    min -= SCALE;
+    max += SCALE + Vec2::Y * 64.0;
+
+    let size = max - min;
+    let scale = (size / window_size).max_element() * 1.1;
+    projection.scale = scale.clamp(MIN_ZOOM, MAX_ZOOM);
+    let center = (min + max) / 2.0;
+    transform.translation.x = center.x;
+    transform.translation.y = center.y;
+}
+
+/// two finger pinch to zoom on touch screens
+#[cfg(target_arch = "wasm32")]
+fn pinch_zoom(
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let touches = touches.iter().collect::<Vec<_>>();
+    if let [first, second] = touches.as_slice() {
+        let distance = first.position().distance(second.position());
+        let previous = first
+            .previous_position()
+            .distance(second.previous_position());
+        if distance > 0.0 && previous > 0.0 {
+            let (mut transform, mut projection) = camera.single_mut();
+            // touch positions start at the top left
+            let middle = (first.position() + second.position()) / 2.0;
+            let screen = Vec2::new(
+                middle.x - window.width() / 2.0,
+                window.height() / 2.0 - middle.y,
+            );
+            zoom_at(&mut transform, &mut projection, previous / distance, screen);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicewars_core::board::BoardGenSettings;
+
+    /// the world point shown at `screen` pixels from the window centre
+    fn world_at(transform: &Transform, projection: &OrthographicProjection, screen: Vec2) -> Vec2 {
+        transform.translation.truncate() + screen * projection.scale
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let mut transform = Transform::default();
+        let mut projection = OrthographicProjection::default();
+        let cursor = Vec2::new(120.0, -45.0);
+
+        let before = world_at(&transform, &projection, cursor);
+        zoom_at(&mut transform, &mut projection, 0.5, cursor);
+        assert_eq!(projection.scale, 0.5);
+        assert!(world_at(&transform, &projection, cursor).abs_diff_eq(before, 1e-4));
+
+        zoom_at(&mut transform, &mut projection, 1000.0, cursor);
+        assert_eq!(projection.scale, MAX_ZOOM);
+        assert!(world_at(&transform, &projection, cursor).abs_diff_eq(before, 1e-3));
+
+        zoom_at(&mut transform, &mut projection, 0.0, cursor);
+        assert_eq!(projection.scale, MIN_ZOOM);
+    }
+
+    #[test]
+    fn fitting_keeps_every_hex_in_view() {
+        let board = Board::generate(&BoardGenSettings {
+            seed: Some(6),
+            ..Default::default()
+        })
+        .unwrap();
+        for window_size in [Vec2::new(1280.0, 720.0), Vec2::new(400.0, 900.0)] {
+            let mut transform = Transform::default();
+            let mut projection = OrthographicProjection::default();
+            fit(&mut transform, &mut projection, &board.map, window_size);
+
+            let half_view = window_size / 2.0 * projection.scale;
+            for hex in board.map.tiles.keys() {
+                let offset = Vec2::from(hex.to_grid()) * SCALE - transform.translation.truncate();
+                assert!(offset.abs().cmple(half_view - SCALE).all());
+            }
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use super::{
+    board_renderer::{BoardRenderData, LoadBoardEvent, Tile, SCALE},
+    storage,
+};
+use bevy::{
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    ui::{FocusPolicy, Interaction},
+};
+use bevy_egui::{
+    egui::{self, Color32, RichText, Slider},
+    EguiContext,
+};
+use bevy_mod_picking::*;
+use dicewars_core::{
+    agent::{BotKind, Seat},
+    board::{Board, BoardGenSettings, Goal, WinCondition, MAX_PLAYERS},
+    hex::Hex,
+    scenario::{Scenario, ScenarioTerritory},
+};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MapEditor {
+            scenario: Scenario::new(2, Default::default()),
+            active: false,
+            tool: EditTool::Paint,
+            territory: None,
+            player: 0,
+            dice: 1,
+            live: None,
+        })
+        .add_startup_system(setup)
+        .add_system(editor_ui_system)
+        .add_system(editor_input)
+        .add_system(update_cells);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditTool {
+    /// add hexes to the current territory
+    Paint,
+    Erase,
+    /// make the clicked territory the current one
+    Select,
+    /// give the clicked territory to the current player
+    Owner,
+    /// set the clicked territory's dice
+    Dice,
+}
+
+/// hand editing a map on the board, the game being played is put aside until the editor is closed
+pub struct MapEditor {
+    pub scenario: Scenario,
+    active: bool,
+    pub tool: EditTool,
+    /// territory being painted, `None` starts a new one on the next paint
+    pub territory: Option<usize>,
+    /// owner given to new and clicked territories
+    pub player: usize,
+    /// dice given to new and clicked territories
+    pub dice: u32,
+    live: Option<Board>,
+}
+
+impl MapEditor {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// start editing a copy of the current board
+    pub fn open(&mut self, board: &mut Board) {
+        self.scenario = Scenario::from_board(board);
+        self.scenario.player_count = self.scenario.player_count.max(2);
+        self.live = Some(board.clone());
+        self.active = true;
+        self.territory = None;
+        self.player = 0;
+        *board = Board::from_scenario(&self.scenario, 0);
+    }
+
+    pub fn close(&mut self, board: &mut Board) {
+        if let Some(live) = self.live.take() {
+            *board = live;
+        }
+        self.active = false;
+    }
+
+    /// applies the current tool to a hex, returns whether the map changed
+    fn apply(&mut self, hex: Hex) -> bool {
+        let clicked = self.scenario.territory_at(hex);
+        match self.tool {
+            EditTool::Paint => {
+                let territory = match self.territory {
+                    Some(territory) if territory < self.scenario.territories.len() => territory,
+                    _ => {
+                        self.scenario.territories.push(ScenarioTerritory {
+                            tiles: Vec::new(),
+                            owner: self.player,
+                            dice: self.dice,
+                        });
+                        self.scenario.territories.len() - 1
+                    }
+                };
+                let changed = clicked != Some(territory);
+                self.territory = Some(self.scenario.paint(hex, territory));
+                changed
+            }
+            EditTool::Erase => match self.scenario.erase(hex) {
+                // keep pointing at the same territory after the ones after it move down
+                Some(removed) => {
+                    self.territory = match self.territory {
+                        Some(territory) if territory == removed => None,
+                        Some(territory) if territory > removed => Some(territory - 1),
+                        territory => territory,
+                    };
+                    true
+                }
+                None => clicked.is_some(),
+            },
+            EditTool::Select => {
+                if clicked.is_some() {
+                    self.territory = clicked;
+                }
+                false
+            }
+            EditTool::Owner => match clicked {
+                Some(index) if self.scenario.territories[index].owner != self.player => {
+                    self.scenario.territories[index].owner = self.player;
+                    true
+                }
+                _ => false,
+            },
+            EditTool::Dice => match clicked {
+                Some(index) if self.scenario.territories[index].dice != self.dice => {
+                    self.scenario.territories[index].dice = self.dice;
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+/// empty hex that can be painted on while the editor is open
+#[derive(Component)]
+struct EditorCell {
+    hex: Hex,
+}
+
+struct CellAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(CellAssets {
+        mesh: asset_server.load("hexagon.obj"),
+        material: material_assets.add(ColorMaterial::from(Color::rgb_u8(230, 230, 230))),
+    });
+}
+
+/// spawns a blank hex under every spot on the board while the editor is open
+fn update_cells(
+    mut commands: Commands,
+    editor: Res<MapEditor>,
+    board_gen_settings: Res<BoardGenSettings>,
+    cell_assets: Res<CellAssets>,
+    cells: Query<Entity, With<EditorCell>>,
+) {
+    let spawned = !cells.is_empty();
+    if editor.is_active() && (!spawned || board_gen_settings.is_changed()) {
+        for cell in cells.iter() {
+            commands.entity(cell).despawn();
+        }
+        for hex in Hex::disc(board_gen_settings.board_size as i32) {
+            let center = Vec2::from(hex.to_grid());
+            // behind the territory tiles so those get picked first
+            let transform = Transform::from_translation((center * SCALE).extend(-1.0))
+                .with_scale(SCALE.extend(1.0) * 0.9);
+            commands
+                .spawn_bundle(MaterialMesh2dBundle {
+                    transform,
+                    mesh: cell_assets.mesh.clone().into(),
+                    material: cell_assets.material.clone(),
+                    ..default()
+                })
+                .insert_bundle((
+                    EditorCell { hex },
+                    PickableMesh::default(),
+                    Hover::default(),
+                    FocusPolicy::Block,
+                    Interaction::None,
+                ));
+        }
+    } else if !editor.is_active() && spawned {
+        for cell in cells.iter() {
+            commands.entity(cell).despawn();
+        }
+    }
+}
+
+/// paints with the current tool while the mouse is held over the board
+fn editor_input(
+    mut editor: ResMut<MapEditor>,
+    mut board: ResMut<Board>,
+    mouse: Res<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    tiles: Query<(&Tile, &Interaction)>,
+    cells: Query<(&EditorCell, &Interaction)>,
+) {
+    if !editor.is_active()
+        || !mouse.pressed(MouseButton::Left)
+        || egui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+
+    // the hex under the mouse is clicked rather than hovered while the button is held
+    let hovered = tiles
+        .iter()
+        .find(|(_, interaction)| **interaction != Interaction::None)
+        .map(|(tile, _)| tile.hex)
+        .or_else(|| {
+            cells
+                .iter()
+                .find(|(_, interaction)| **interaction != Interaction::None)
+                .map(|(cell, _)| cell.hex)
+        });
+    if let Some(hex) = hovered {
+        if editor.apply(hex) {
+            *board = Board::from_scenario(&editor.scenario, 0);
+        }
+    }
+}
+
+fn editor_ui_system(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<MapEditor>,
+    mut board: ResMut<Board>,
+    board_render_data: Res<BoardRenderData>,
+    mut load_board_event: EventWriter<LoadBoardEvent>,
+    mut status: Local<String>,
+) {
+    if !editor.is_active() {
+        return;
+    }
+
+    let mut changed = false;
+    egui::Window::new("Map editor").show(egui_context.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for (tool, name) in [
+                (EditTool::Paint, "Paint"),
+                (EditTool::Erase, "Erase"),
+                (EditTool::Select, "Select"),
+                (EditTool::Owner, "Owner"),
+                (EditTool::Dice, "Dice"),
+            ] {
+                ui.selectable_value(&mut editor.tool, tool, name);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(match editor.territory {
+                Some(territory) => format!("Territory {}", territory),
+                None => "New territory".to_string(),
+            });
+            if ui.button("New territory").clicked() {
+                editor.territory = None;
+                editor.tool = EditTool::Paint;
+            }
+        });
+
+        let player_count = &mut editor.scenario.player_count;
+        changed |= ui
+            .add(Slider::new(player_count, 2..=MAX_PLAYERS).text("Players"))
+            .changed();
+        editor.player = editor.player.min(editor.scenario.player_count - 1);
+        let colour = board_render_data.colours[editor.player];
+        egui::ComboBox::from_label("Player")
+            .selected_text(RichText::new(format!("Player {}", editor.player)).color(
+                Color32::from_rgb(
+                    (colour.r() * 255.0) as u8,
+                    (colour.g() * 255.0) as u8,
+                    (colour.b() * 255.0) as u8,
+                ),
+            ))
+            .show_ui(ui, |ui| {
+                for player in 0..editor.scenario.player_count {
+                    ui.selectable_value(&mut editor.player, player, format!("Player {}", player));
+                }
+            });
+        let max_dice = editor.scenario.rules.max_dice;
+        ui.add(Slider::new(&mut editor.dice, 1..=max_dice).text("Dice"));
+
+        ui.collapsing("Seats", |ui| {
+            let scenario = &mut editor.scenario;
+            let mut own_seats = !scenario.seats.is_empty();
+            ui.checkbox(&mut own_seats, "Scenario picks who plays");
+            if own_seats {
+                scenario.seats.resize(scenario.player_count, Seat::Human);
+            } else {
+                scenario.seats.clear();
+            }
+            for (player, seat) in scenario.seats.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("scenario seat", player))
+                    .selected_text(match seat {
+                        Seat::Human => format!("Player {}: Human", player),
+                        Seat::Bot(kind) => format!("Player {}: {} bot", player, kind.name()),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(seat, Seat::Human, "Human");
+                        for kind in BotKind::ALL {
+                            ui.selectable_value(
+                                seat,
+                                Seat::Bot(kind),
+                                format!("{} bot", kind.name()),
+                            );
+                        }
+                    });
+            }
+        });
+
+        ui.collapsing("Win conditions", |ui| {
+            ui.label("The last player left always wins");
+            let scenario = &mut editor.scenario;
+            let player_count = scenario.player_count;
+            let mut removed = None;
+            for (index, condition) in scenario.win_conditions.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(("condition player", index))
+                        .selected_text(match condition.player {
+                            Some(player) => format!("Player {}", player),
+                            None => "Anyone".to_string(),
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut condition.player, None, "Anyone");
+                            for player in 0..player_count {
+                                ui.selectable_value(
+                                    &mut condition.player,
+                                    Some(player),
+                                    format!("Player {}", player),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_source(("condition goal", index))
+                        .selected_text(condition.goal.name())
+                        .show_ui(ui, |ui| {
+                            for goal in
+                                [Goal::Eliminate, Goal::HoldTerritories(10), Goal::Survive(5)]
+                            {
+                                // keep the number when picking the same goal again
+                                let selected = goal.name() == condition.goal.name();
+                                if ui.selectable_label(selected, goal.name()).clicked() && !selected
+                                {
+                                    condition.goal = goal;
+                                }
+                            }
+                        });
+                    match &mut condition.goal {
+                        Goal::Eliminate => {}
+                        Goal::HoldTerritories(count) => {
+                            ui.add(egui::DragValue::new(count).clamp_range(1..=200));
+                        }
+                        Goal::Survive(turns) => {
+                            ui.add(egui::DragValue::new(turns).clamp_range(1..=200));
+                        }
+                    }
+                    if ui.button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                scenario.win_conditions.remove(index);
+            }
+            if ui.button("Add condition").clicked() {
+                scenario.win_conditions.push(WinCondition {
+                    player: None,
+                    goal: Goal::HoldTerritories(10),
+                });
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Clear").clicked() {
+                let rules = editor.scenario.rules;
+                editor.scenario = Scenario::new(editor.scenario.player_count, rules);
+                editor.territory = None;
+                changed = true;
+            }
+            // scenario files have to be playable to be loaded again
+            if ui
+                .add_enabled(
+                    editor.scenario.validate().is_ok(),
+                    egui::Button::new("Save"),
+                )
+                .clicked()
+            {
+                *status = match storage::write(storage::SCENARIO_NAME, &editor.scenario.to_json()) {
+                    Ok(()) => "Saved".to_string(),
+                    Err(error) => error,
+                };
+            }
+            if ui.button("Load").clicked() {
+                let loaded = storage::read(storage::SCENARIO_NAME)
+                    .and_then(|json| Scenario::from_json(&json).map_err(|error| error.to_string()));
+                *status = match loaded {
+                    Ok(scenario) => {
+                        editor.scenario = scenario;
+                        editor.territory = None;
+                        changed = true;
+                        "Loaded".to_string()
+                    }
+                    Err(error) => error,
+                };
+            }
+        });
+        ui.label(status.as_str());
+
+        let valid = editor.scenario.validate();
+        if let Err(error) = valid {
+            ui.label(RichText::new(error.to_string()).color(Color32::RED));
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(valid.is_ok(), egui::Button::new("Play"))
+                .clicked()
+            {
+                // rolls follow on from the game the editor was opened over so seeded games stay
+                // reproducible
+                let seed = editor.live.as_ref().map_or(0, |live| live.seed);
+                if let Ok(new_board) = editor.scenario.to_board(seed) {
+                    // the live game is replaced so there's nothing to go back to
+                    editor.live = None;
+                    editor.active = false;
+                    status.clear();
+                    let seats = Some(editor.scenario.seats.clone()).filter(|s| !s.is_empty());
+                    load_board_event.send(LoadBoardEvent {
+                        board: new_board,
+                        seats,
+                        start: None,
+                    });
+                }
+            }
+            if ui.button("Back to game").clicked() {
+                editor.close(&mut board);
+                status.clear();
+            }
+        });
+    });
+
+    if changed && editor.is_active() {
+        *board = Board::from_scenario(&editor.scenario, 0);
+    }
+}
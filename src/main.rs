@@ -1,13 +1,26 @@
-use bevy::{prelude::*, ui::Interaction, winit::WinitSettings};
+use animation::AttackAnimation;
+use bevy::{prelude::*, ui::Interaction, window::RequestRedraw, winit::WinitSettings};
 use bevy_mod_picking::*;
-use board::Board;
-use board_renderer::{BoardRenderData, Tile};
+use board_renderer::{BoardRenderData, LoadBoardEvent, RegenerateBoardEvent, Tile};
+use dicewars_core::{
+    agent::{Action, Agent, Seat},
+    board::{AttackOutcome, Board, BoardGenSettings},
+    expectimax::ExpectimaxAgent,
+    replay::Replay,
+};
+use editor::MapEditor;
+use replay::ReplayPlayback;
+use undo::{UndoHistory, UndoMode};
 
-mod board;
+mod animation;
 mod board_renderer;
+mod camera;
+mod editor;
 mod fps_counter;
-mod hex;
+mod replay;
+mod storage;
 mod ui;
+mod undo;
 
 fn main() {
     let mut app = App::new();
@@ -21,6 +34,11 @@ fn main() {
         .add_plugin(ui::UiPlugin)
         .add_plugin(board_renderer::BoardPlugin)
         .add_plugin(fps_counter::FpsCounter)
+        .add_plugin(replay::ReplayPlugin)
+        .add_plugin(undo::UndoPlugin)
+        .add_plugin(editor::EditorPlugin)
+        .add_plugin(animation::AnimationPlugin)
+        .add_plugin(camera::CameraPlugin)
         .add_plugins(DefaultPickingPlugins)
         .add_event::<GameStateEvent>()
         .insert_resource(WinitSettings {
@@ -33,24 +51,64 @@ fn main() {
             ..Default::default()
         })
         .insert_resource(SelectionState { current: None })
+        .insert_resource(AttackLog {
+            attacks: Vec::new(),
+        })
+        .insert_resource(GameStart {
+            board: Board::default(),
+        })
+        .insert_resource(Bots {
+            seats: Vec::new(),
+            agents: Vec::new(),
+            delay: 0.5,
+            timer: 0.0,
+        })
         .insert_resource(ClearColor(Color::rgb_u8(255, 255, 255)))
-        .add_startup_system(setup)
         .add_system(process_game)
+        .add_system(run_bots)
+        .add_system(detect_game_over)
         .run();
 }
 
-fn setup(mut commands: Commands) {
-    commands
-        .spawn_bundle(Camera2dBundle::default())
-        .insert_bundle(PickingCameraBundle::default());
-}
-
 struct SelectionState {
     current: Option<usize>,
 }
 
+/// every attack made this game, most recent last
+pub struct AttackLog {
+    pub attacks: Vec<AttackOutcome>,
+}
+
+/// the board the current game started from, kept in saves so a rematch of a loaded game starts
+/// over from the beginning instead of from where it was saved
+pub struct GameStart {
+    pub board: Board,
+}
+
+/// computer opponents for the current game
+pub struct Bots {
+    /// who plays each player in the current game
+    seats: Vec<Seat>,
+    /// indexed by player, `None` for human players. made from `seats` once the new board is in
+    /// place so they can be seeded from it and games with the same seed play out the same
+    agents: Vec<Option<Box<dyn Agent>>>,
+    /// seconds between bot actions
+    pub delay: f32,
+    timer: f32,
+}
+
+impl Bots {
+    pub fn is_bot(&self, player: usize) -> bool {
+        matches!(self.seats.get(player), Some(Seat::Bot(_)))
+    }
+}
+
 pub enum GameStateEvent {
     FinishTurn,
+    Undo,
+    Redo,
+    /// sent once when the game being played is won
+    GameOver,
 }
 
 fn process_game(
@@ -60,8 +118,23 @@ fn process_game(
     mut board: ResMut<Board>,
     mut game_state_events: EventReader<GameStateEvent>,
     mut board_render_data: ResMut<BoardRenderData>,
+    mut attack_log: ResMut<AttackLog>,
+    bots: Res<Bots>,
+    mut replay: ResMut<Replay>,
+    playback: Res<ReplayPlayback>,
+    mut history: ResMut<UndoHistory>,
+    editor: Res<MapEditor>,
+    mut animation: ResMut<AttackAnimation>,
 ) {
+    // nobody plays while a replay is being watched, the map is being edited or an attack is shown
+    let live =
+        !playback.is_active() && !editor.is_active() && !animation.is_playing() && !board.is_over();
+    let human_turn = live && !bots.is_bot(board.current_player());
+
     for event in events.iter() {
+        if !human_turn {
+            continue;
+        }
         if let PickingEvent::Clicked(e) = event {
             for (tile, _, entity) in tile_entitys.iter() {
                 if entity == *e {
@@ -70,6 +143,7 @@ fn process_game(
                             if board.owner(tile.index) == board.current_player() {
                                 let available_moves = board.available_moves(tile.index);
                                 if available_moves.len() > 0 {
+                                    history.record(&board, selection_state.current, &replay);
                                     selection_state.current = Some(tile.index);
                                     board_render_data.selected = Some(tile.index);
                                     board_render_data.attackable = available_moves;
@@ -78,6 +152,7 @@ fn process_game(
                         }
                         Some(first) => {
                             if first == tile.index {
+                                history.record(&board, selection_state.current, &replay);
                                 selection_state.current = None;
                                 board_render_data.selected = None;
                                 board_render_data.attackable = Vec::new();
@@ -85,8 +160,22 @@ fn process_game(
                             }
 
                             let second = tile.index;
-                            if board.available_moves(first).contains(&second) {
-                                board.make_move(first, second);
+                            if !history.allows_attack(first, second, &replay) {
+                                continue;
+                            }
+                            let before = board.clone();
+                            if let Ok(outcome) = board.try_attack(first, second) {
+                                match history.mode {
+                                    UndoMode::Casual => {
+                                        history.record(&before, Some(first), &replay);
+                                        history.record_attack(first, second, &replay);
+                                    }
+                                    // can't undo past an attack
+                                    _ => history.reset(replay.actions.len()),
+                                }
+                                replay.record_attack(&outcome);
+                                animation.play(&before, &outcome);
+                                attack_log.attacks.push(outcome);
 
                                 selection_state.current = None;
                                 board_render_data.selected = None;
@@ -112,9 +201,135 @@ fn process_game(
     }
 
     for game_state_event in game_state_events.iter() {
+        if !human_turn {
+            continue;
+        }
         match game_state_event {
             GameStateEvent::FinishTurn => {
                 board.finish_turn();
+                replay.record_end_turn();
+                history.reset(replay.actions.len());
+
+                selection_state.current = None;
+                board_render_data.selected = None;
+                board_render_data.attackable = Vec::new();
+            }
+            GameStateEvent::Undo | GameStateEvent::Redo => {
+                let mut selection = selection_state.current;
+                if let GameStateEvent::Undo = game_state_event {
+                    history.undo(&mut board, &mut selection, &mut replay, &mut attack_log);
+                } else {
+                    history.redo(&mut board, &mut selection, &mut replay, &mut attack_log);
+                }
+
+                selection_state.current = selection;
+                board_render_data.selected = selection;
+                board_render_data.attackable = selection
+                    .map(|selection| board.available_moves(selection))
+                    .unwrap_or_default();
+            }
+            GameStateEvent::GameOver => {}
+        }
+    }
+}
+
+fn detect_game_over(
+    board: Res<Board>,
+    playback: Res<ReplayPlayback>,
+    editor: Res<MapEditor>,
+    animation: Res<AttackAnimation>,
+    mut game_state_events: EventWriter<GameStateEvent>,
+    mut announced: Local<bool>,
+) {
+    // finished boards shown by a replay or the editor aren't games being played
+    let over = board.winner().is_some() && !playback.is_active() && !editor.is_active();
+    // let the winning attack play out first
+    if animation.is_playing() {
+        return;
+    }
+    if over && !*announced {
+        game_state_events.send(GameStateEvent::GameOver);
+    }
+    *announced = over;
+}
+
+fn run_bots(
+    mut board: ResMut<Board>,
+    mut bots: ResMut<Bots>,
+    time: Res<Time>,
+    mut regenerate_board_events: EventReader<RegenerateBoardEvent>,
+    mut load_board_events: EventReader<LoadBoardEvent>,
+    board_gen_settings: Res<BoardGenSettings>,
+    mut redraw_events: EventWriter<RequestRedraw>,
+    mut replay: ResMut<Replay>,
+    mut attack_log: ResMut<AttackLog>,
+    playback: Res<ReplayPlayback>,
+    mut history: ResMut<UndoHistory>,
+    editor: Res<MapEditor>,
+    mut animation: ResMut<AttackAnimation>,
+) {
+    // pick bots for the new game, scenarios can choose their own seats
+    let mut seats = None;
+    let mut new_game = regenerate_board_events.iter().count() > 0;
+    for event in load_board_events.iter() {
+        seats = event.seats.clone();
+        new_game = true;
+    }
+    if new_game {
+        bots.seats = seats.unwrap_or_else(|| board_gen_settings.seats.clone());
+        bots.agents.clear();
+        bots.timer = 0.0;
+        return;
+    }
+
+    // the new board has been put in place by now
+    if bots.agents.is_empty() {
+        bots.agents = bots
+            .seats
+            .iter()
+            .enumerate()
+            .map(|(player, seat)| match seat {
+                Seat::Human => None,
+                Seat::Bot(kind) => Some(kind.create(
+                    board.seed ^ player as u64,
+                    Some(ExpectimaxAgent::FRAME_TIME),
+                )),
+            })
+            .collect();
+    }
+
+    // let bots play their turn
+    let live =
+        !playback.is_active() && !editor.is_active() && !animation.is_playing() && !board.is_over();
+    if live && bots.is_bot(board.current_player()) {
+        // keep updating while the window is in low power mode
+        redraw_events.send(RequestRedraw);
+
+        bots.timer += time.delta_seconds();
+        if bots.timer >= bots.delay {
+            bots.timer = 0.0;
+            let player = board.current_player();
+            let action = bots.agents[player].as_mut().unwrap().next_action(&board);
+            let before = board.clone();
+            match action {
+                Action::Attack(first, second) => match board.try_attack(first, second) {
+                    Ok(outcome) => {
+                        replay.record_attack(&outcome);
+                        animation.play(&before, &outcome);
+                        attack_log.attacks.push(outcome);
+                    }
+                    // end the turn so a bot making invalid moves can't stall the game
+                    Err(_) => {
+                        board.finish_turn();
+                        replay.record_end_turn();
+                        history.reset(replay.actions.len());
+                    }
+                },
+                Action::EndTurn => {
+                    board.finish_turn();
+                    replay.record_end_turn();
+                    history.reset(replay.actions.len());
+                }
             }
         }
     }
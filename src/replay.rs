@@ -0,0 +1,103 @@
+use super::board_renderer::{LoadBoardEvent, RegenerateBoardEvent};
+use bevy::{prelude::*, window::RequestRedraw};
+use dicewars_core::{board::Board, replay::Replay, save::SaveError};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ReplayPlayback {
+            states: Vec::new(),
+            index: 0,
+            playing: false,
+            speed: 4.0,
+            timer: 0.0,
+            live: None,
+        })
+        .add_system(playback_system);
+    }
+}
+
+/// watching a replay on the board, the game being played is put aside until the replay is closed
+pub struct ReplayPlayback {
+    /// the board before the first action and after each action, empty when no replay is open
+    states: Vec<Board>,
+    index: usize,
+    pub playing: bool,
+    /// actions per second
+    pub speed: f32,
+    timer: f32,
+    live: Option<Board>,
+}
+
+impl ReplayPlayback {
+    pub fn is_active(&self) -> bool {
+        !self.states.is_empty()
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// index of the final board
+    pub fn last(&self) -> usize {
+        self.states.len().saturating_sub(1)
+    }
+
+    pub fn open(&mut self, replay: &Replay, board: &mut Board) -> Result<(), SaveError> {
+        self.states = replay.states()?;
+        if self.live.is_none() {
+            self.live = Some(board.clone());
+        }
+        self.playing = false;
+        self.timer = 0.0;
+        self.seek(0, board);
+        Ok(())
+    }
+
+    pub fn close(&mut self, board: &mut Board) {
+        if let Some(live) = self.live.take() {
+            *board = live;
+        }
+        self.states = Vec::new();
+        self.playing = false;
+    }
+
+    pub fn seek(&mut self, index: usize, board: &mut Board) {
+        self.index = index.min(self.last());
+        *board = self.states[self.index].clone();
+    }
+}
+
+fn playback_system(
+    mut playback: ResMut<ReplayPlayback>,
+    mut board: ResMut<Board>,
+    time: Res<Time>,
+    mut regenerate_board_events: EventReader<RegenerateBoardEvent>,
+    mut load_board_events: EventReader<LoadBoardEvent>,
+    mut redraw_events: EventWriter<RequestRedraw>,
+) {
+    // starting another game closes the replay without going back to the old one
+    if regenerate_board_events.iter().count() + load_board_events.iter().count() > 0 {
+        playback.states = Vec::new();
+        playback.live = None;
+        playback.playing = false;
+        return;
+    }
+
+    if playback.is_active() && playback.playing {
+        // keep updating while the window is in low power mode
+        redraw_events.send(RequestRedraw);
+
+        playback.timer += time.delta_seconds();
+        if playback.timer >= 1.0 / playback.speed {
+            playback.timer = 0.0;
+            if playback.index < playback.last() {
+                let index = playback.index + 1;
+                playback.seek(index, &mut board);
+            } else {
+                playback.playing = false;
+            }
+        }
+    }
+}
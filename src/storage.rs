@@ -0,0 +1,42 @@
+//! where save games, replays and scenarios are kept, files in the working directory on desktop and local
+//! storage in the browser
+
+pub const SAVE_NAME: &str = "dicewars_save.json";
+pub const REPLAY_NAME: &str = "dicewars_replay.json";
+pub const SCENARIO_NAME: &str = "dicewars_scenario.json";
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(name: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(name, contents).map_err(|error| error.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name: &str) -> Result<String, String> {
+    std::fs::read_to_string(name).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .ok_or("no browser window")?
+        .local_storage()
+        .ok()
+        .flatten()
+        .ok_or_else(|| "local storage is unavailable".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(name: &str, contents: &str) -> Result<(), String> {
+    local_storage()?
+        .set_item(name, contents)
+        .map_err(|_| "couldn't write to local storage".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(name: &str) -> Result<String, String> {
+    local_storage()?
+        .get_item(name)
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("{} not found", name))
+}
@@ -1,49 +1,146 @@
 use super::{
-    board::{Board, BoardGenSettings},
-    board_renderer::{BoardRenderData, RegenerateBoardEvent},
-    GameStateEvent,
+    animation::AttackAnimation,
+    board_renderer::{
+        BoardRenderData, GenerateStatus, LoadBoardEvent, Palette, RegenerateBoardEvent,
+    },
+    editor::MapEditor,
+    replay::ReplayPlayback,
+    storage,
+    undo::{UndoHistory, UndoMode},
+    AttackLog, Bots, GameStart, GameStateEvent,
 };
 use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, Slider},
     EguiContext, EguiPlugin,
 };
+use dicewars_core::{
+    agent::{BotKind, Seat},
+    board::{Board, BoardGenSettings, GameOutcome, Goal, RuleSet, TieBreak, MAX_PLAYERS},
+    generator::GeneratorSettings,
+    replay::{PlayerStats, Replay},
+    save::SaveGame,
+};
 use egui::{Color32, RichText};
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(EguiPlugin).add_system(ui_system);
+        app.add_plugin(EguiPlugin)
+            .add_system(ui_system)
+            .add_system(replay_ui_system)
+            .add_system(game_over_ui_system);
     }
 }
 
 fn ui_system(
     mut egui_context: ResMut<EguiContext>,
-    board: Res<Board>,
-    board_render_data: Res<BoardRenderData>,
+    mut board: ResMut<Board>,
+    mut board_render_data: ResMut<BoardRenderData>,
     mut game_state_events: EventWriter<GameStateEvent>,
     mut regenerate_board_event: EventWriter<RegenerateBoardEvent>,
     mut board_gen_settings: ResMut<BoardGenSettings>,
+    attack_log: Res<AttackLog>,
+    mut seed_text: Local<String>,
+    // grouped to stay within the number of parameters a system can take
+    (mut bots, game_start): (ResMut<Bots>, Res<GameStart>),
+    mut load_board_event: EventWriter<LoadBoardEvent>,
+    mut save_status: Local<String>,
+    mut history: ResMut<UndoHistory>,
+    generate_status: Res<GenerateStatus>,
+    mut editor: ResMut<MapEditor>,
+    playback: Res<ReplayPlayback>,
+    mut animation: ResMut<AttackAnimation>,
 ) {
+    // the editor has its own panel
+    if editor.is_active() {
+        return;
+    }
+
     egui::Window::new("Game menu").show(egui_context.ctx_mut(), |ui| {
-        if ui.button("New game").clicked() {
-            regenerate_board_event.send(RegenerateBoardEvent);
+        let settings_error = board_gen_settings.validate().err();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(settings_error.is_none(), egui::Button::new("New game"))
+                .clicked()
+            {
+                regenerate_board_event.send(RegenerateBoardEvent);
+            }
+            // the replay would keep moving the board while it's being edited
+            if ui
+                .add_enabled(!playback.is_active(), egui::Button::new("Map editor"))
+                .clicked()
+            {
+                editor.open(&mut board);
+            }
+        });
+        if let Some(error) = settings_error.or(generate_status.error) {
+            ui.label(RichText::new(error.to_string()).color(Color32::RED));
         }
 
-        if ui.button("Finish turn").clicked() {
-            game_state_events.send(GameStateEvent::FinishTurn);
-        }
+        ui.horizontal(|ui| {
+            if ui.button("Finish turn").clicked() {
+                game_state_events.send(GameStateEvent::FinishTurn);
+            }
+            if ui
+                .add_enabled(history.can_undo(), egui::Button::new("Undo"))
+                .clicked()
+            {
+                game_state_events.send(GameStateEvent::Undo);
+            }
+            if ui
+                .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+                .clicked()
+            {
+                game_state_events.send(GameStateEvent::Redo);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            // the board is showing a replay frame instead of the game while a replay is watched
+            let watching = playback.is_active();
+            if ui
+                .add_enabled(!watching, egui::Button::new("Save"))
+                .clicked()
+            {
+                let save = SaveGame::new(&board, &bots.seats, &game_start.board);
+                *save_status = match storage::write(storage::SAVE_NAME, &save.to_json()) {
+                    Ok(()) => "Saved".to_string(),
+                    Err(error) => error,
+                };
+            }
+            if ui
+                .add_enabled(!watching, egui::Button::new("Load"))
+                .clicked()
+            {
+                let save = storage::read(storage::SAVE_NAME)
+                    .and_then(|json| SaveGame::from_json(&json).map_err(|error| error.to_string()));
+                *save_status = match save {
+                    Ok(save) => {
+                        load_board_event.send(LoadBoardEvent {
+                            board: save.board,
+                            seats: Some(save.seats).filter(|seats| !seats.is_empty()),
+                            start: save.start,
+                        });
+                        "Loaded".to_string()
+                    }
+                    Err(error) => error,
+                };
+            }
+            ui.label(save_status.as_str());
+        });
 
         ui.horizontal(|ui| {
             let (turn, scores) = board.scores();
             for i in 0..scores.len() {
                 let colour = board_render_data.colours[scores[i].0];
+                let name = &board_render_data.names[scores[i].0];
                 ui.label(
                     RichText::new(if i == turn {
-                        format!("({:?})", scores[i].1)
+                        format!("({}: {:?})", name, scores[i].1)
                     } else {
-                        format!("{:?}", scores[i].1)
+                        format!("{}: {:?}", name, scores[i].1)
                     })
                     .color(Color32::from_rgb(
                         (colour.r() * 255.0) as u8,
@@ -54,7 +151,387 @@ fn ui_system(
             }
         });
 
-        ui.add(Slider::new(&mut board_gen_settings.player_count, 1..=8).text("Players"));
+        ui.horizontal(|ui| {
+            ui.label("Stockpile:");
+            for player in board.player_order.iter() {
+                let colour = board_render_data.colours[*player];
+                ui.label(
+                    RichText::new(format!("{}", board.stockpiles[*player])).color(
+                        Color32::from_rgb(
+                            (colour.r() * 255.0) as u8,
+                            (colour.g() * 255.0) as u8,
+                            (colour.b() * 255.0) as u8,
+                        ),
+                    ),
+                );
+            }
+        });
+
+        if let Some(attack) = attack_log.attacks.last() {
+            let format_rolls = |rolls: &Vec<u32>| {
+                rolls
+                    .iter()
+                    .map(|roll| roll.to_string())
+                    .collect::<Vec<_>>()
+                    .join("+")
+            };
+            ui.label(format!(
+                "{} = {} vs {} = {}: {}",
+                format_rolls(&attack.attacker_rolls),
+                attack.attacker_total,
+                format_rolls(&attack.defender_rolls),
+                attack.defender_total,
+                if attack.win { "win!" } else { "loss..." },
+            ));
+        }
+
+        ui.add(Slider::new(&mut board_gen_settings.player_count, 2..=MAX_PLAYERS).text("Players"));
         ui.add(Slider::new(&mut board_gen_settings.board_size, 7..=50).text("Board size"));
+        ui.add(Slider::new(&mut board_gen_settings.num_territories, 1..=100).text("Territories"));
+        ui.add(Slider::new(&mut board_gen_settings.territory_size, 1..=30).text("Territory size"));
+        ui.add(Slider::new(&mut board_gen_settings.average_dice, 1..=16).text("Starting dice"));
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Seed: {}", board.seed));
+            if ui.button("Reuse").clicked() {
+                *seed_text = board.seed.to_string();
+                board_gen_settings.seed = Some(board.seed);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Next seed:");
+            if ui.text_edit_singleline(&mut *seed_text).changed() {
+                // leave empty for a random seed
+                board_gen_settings.seed = seed_text.trim().parse().ok();
+            }
+        });
+
+        ui.collapsing("Map", |ui| {
+            ui.label(format!("Fairness: {:.2}", board.fairness()));
+            ui.checkbox(&mut board_gen_settings.balanced, "Balanced");
+            ui.horizontal(|ui| {
+                let mut limit = board_gen_settings.max_starting_cluster.is_some();
+                let mut size = board_gen_settings.max_starting_cluster.unwrap_or(3);
+                let changed = ui.checkbox(&mut limit, "Limit starting clusters").changed()
+                    | ui.add_enabled(limit, Slider::new(&mut size, 1..=10))
+                        .changed();
+                if changed {
+                    board_gen_settings.max_starting_cluster = limit.then_some(size);
+                }
+            });
+
+            let generator = &mut board_gen_settings.generator;
+            egui::ComboBox::from_label("Generator")
+                .selected_text(generator.name())
+                .show_ui(ui, |ui| {
+                    for kind in GeneratorSettings::ALL {
+                        // keep the current parameters if the same kind is picked again
+                        let selected = kind.name() == generator.name();
+                        if ui.selectable_label(selected, kind.name()).clicked() && !selected {
+                            *generator = kind;
+                        }
+                    }
+                });
+            match generator {
+                GeneratorSettings::Grow(_) => {}
+                GeneratorSettings::Voronoi(voronoi) => {
+                    ui.add(Slider::new(&mut voronoi.radius, 1..=8).text("Territory radius"));
+                }
+                GeneratorSettings::Islands(islands) => {
+                    ui.add(Slider::new(&mut islands.noise_scale, 1.0..=20.0).text("Noise scale"));
+                    ui.add(Slider::new(&mut islands.water_level, -1.0..=1.0).text("Water level"));
+                }
+                GeneratorSettings::Symmetric(symmetric) => {
+                    egui::ComboBox::from_label("Folds")
+                        .selected_text(symmetric.folds.to_string())
+                        .show_ui(ui, |ui| {
+                            for folds in [2, 3, 6] {
+                                ui.selectable_value(&mut symmetric.folds, folds, folds.to_string());
+                            }
+                        });
+                }
+            }
+        });
+
+        ui.collapsing("Players", |ui| {
+            for player in 0..board_gen_settings.player_count {
+                let colour = board_render_data.colours[player];
+                let seat = &mut board_gen_settings.seats[player];
+                let name = &mut board_render_data.names[player];
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(name).desired_width(80.0));
+                    egui::ComboBox::from_id_source(("seat", player))
+                        .selected_text(
+                            RichText::new(match seat {
+                                Seat::Human => "Human".to_string(),
+                                Seat::Bot(kind) => format!("{} bot", kind.name()),
+                            })
+                            .color(Color32::from_rgb(
+                                (colour.r() * 255.0) as u8,
+                                (colour.g() * 255.0) as u8,
+                                (colour.b() * 255.0) as u8,
+                            )),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(seat, Seat::Human, "Human");
+                            for kind in BotKind::ALL {
+                                ui.selectable_value(
+                                    seat,
+                                    Seat::Bot(kind),
+                                    format!("{} bot", kind.name()),
+                                );
+                            }
+                        });
+                });
+            }
+            ui.add(Slider::new(&mut bots.delay, 0.0..=2.0).text("Bot delay"));
+            ui.horizontal(|ui| {
+                let mut animate = !animation.skip;
+                if ui.checkbox(&mut animate, "Animate attacks").changed() {
+                    animation.skip = !animate;
+                }
+                ui.add_enabled(
+                    animate,
+                    Slider::new(&mut animation.speed, 0.5..=4.0).text("Speed"),
+                );
+            });
+            ui.add(
+                Slider::new(&mut board_render_data.stack_height, 2..=8).text("Dice stack height"),
+            );
+            egui::ComboBox::from_label("Undo")
+                .selected_text(format!("{:?}", history.mode))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut history.mode, UndoMode::Off, "Off");
+                    ui.selectable_value(&mut history.mode, UndoMode::Selection, "Selection");
+                    ui.selectable_value(&mut history.mode, UndoMode::Casual, "Casual");
+                });
+        });
+
+        ui.collapsing("Display", |ui| {
+            let palette = &mut board_render_data.palette;
+            egui::ComboBox::from_label("Colours")
+                .selected_text(palette.name())
+                .show_ui(ui, |ui| {
+                    for option in Palette::ALL {
+                        ui.selectable_value(palette, option, option.name());
+                    }
+                });
+            ui.checkbox(&mut board_render_data.patterns, "Player patterns");
+        });
+
+        ui.collapsing("Rules", |ui| {
+            let rules = &mut board_gen_settings.rules;
+            ui.add(Slider::new(&mut rules.die_faces, 2..=20).text("Die faces"));
+            ui.add(Slider::new(&mut rules.max_dice, 2..=16).text("Max dice"));
+            egui::ComboBox::from_label("Ties")
+                .selected_text(format!("{:?}", rules.tie_break))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut rules.tie_break, TieBreak::Defender, "Defender");
+                    ui.selectable_value(&mut rules.tie_break, TieBreak::Attacker, "Attacker");
+                    ui.selectable_value(&mut rules.tie_break, TieBreak::Random, "Random");
+                });
+            if ui.button("Classic").clicked() {
+                *rules = RuleSet::CLASSIC;
+            }
+        });
     });
 }
+
+fn replay_ui_system(
+    mut egui_context: ResMut<EguiContext>,
+    mut board: ResMut<Board>,
+    replay: Res<Replay>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut status: Local<String>,
+    editor: Res<MapEditor>,
+) {
+    if editor.is_active() {
+        return;
+    }
+
+    egui::Window::new("Replay").show(egui_context.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            // the board is showing a replay frame instead of the game while a replay is watched
+            let watching = playback.is_active();
+            if ui
+                .add_enabled(!watching, egui::Button::new("Save"))
+                .clicked()
+            {
+                *status = match storage::write(storage::REPLAY_NAME, &replay.to_json()) {
+                    Ok(()) => "Saved".to_string(),
+                    Err(error) => error,
+                };
+            }
+            if ui.button("Load").clicked() {
+                let loaded = storage::read(storage::REPLAY_NAME)
+                    .and_then(|json| Replay::from_json(&json).map_err(|error| error.to_string()))
+                    .and_then(|loaded| {
+                        playback
+                            .open(&loaded, &mut board)
+                            .map_err(|error| error.to_string())
+                    });
+                *status = match loaded {
+                    Ok(()) => String::new(),
+                    Err(error) => error,
+                };
+            }
+            if ui.button("Watch this game").clicked() {
+                *status = match playback.open(&replay, &mut board) {
+                    Ok(()) => String::new(),
+                    Err(error) => error.to_string(),
+                };
+            }
+        });
+        ui.label(status.as_str());
+
+        if playback.is_active() {
+            ui.horizontal(|ui| {
+                let index = playback.index();
+                let last = playback.last();
+                if ui.button("|<").clicked() {
+                    playback.seek(0, &mut board);
+                }
+                if ui.button("<").clicked() {
+                    playback.seek(index.saturating_sub(1), &mut board);
+                }
+                let play_text = if playback.playing { "Pause" } else { "Play" };
+                if ui.button(play_text).clicked() {
+                    playback.playing = !playback.playing;
+                }
+                if ui.button(">").clicked() {
+                    playback.seek(index + 1, &mut board);
+                }
+                if ui.button(">|").clicked() {
+                    playback.seek(last, &mut board);
+                }
+                ui.label(format!("{} / {}", playback.index(), last));
+            });
+            ui.add(Slider::new(&mut playback.speed, 0.5..=20.0).text("Actions per second"));
+            if ui.button("Back to game").clicked() {
+                playback.close(&mut board);
+            }
+        }
+    });
+}
+
+/// final stats once someone wins, with buttons to play again
+fn game_over_ui_system(
+    mut egui_context: ResMut<EguiContext>,
+    board: Res<Board>,
+    board_render_data: Res<BoardRenderData>,
+    board_gen_settings: Res<BoardGenSettings>,
+    replay: Res<Replay>,
+    game_start: Res<GameStart>,
+    bots: Res<Bots>,
+    editor: Res<MapEditor>,
+    mut game_state_events: EventReader<GameStateEvent>,
+    mut regenerate_board_event: EventWriter<RegenerateBoardEvent>,
+    mut load_board_event: EventWriter<LoadBoardEvent>,
+    mut stats: Local<Option<Vec<PlayerStats>>>,
+) {
+    for game_state_event in game_state_events.iter() {
+        if let GameStateEvent::GameOver = game_state_event {
+            // the end screen still shows the final board if the replay doesn't play back
+            *stats = Some(replay.player_stats().unwrap_or_default());
+        }
+    }
+
+    // close once a different game is on the board
+    let (winner, goal) = match board.outcome() {
+        GameOutcome::Won { player, goal } => (player, goal),
+        GameOutcome::InProgress => {
+            *stats = None;
+            return;
+        }
+    };
+    if editor.is_active() {
+        return;
+    }
+    let player_stats = match stats.as_ref() {
+        Some(player_stats) => player_stats,
+        None => return,
+    };
+
+    let colour = |player: usize| {
+        let colour = board_render_data.colours[player];
+        Color32::from_rgb(
+            (colour.r() * 255.0) as u8,
+            (colour.g() * 255.0) as u8,
+            (colour.b() * 255.0) as u8,
+        )
+    };
+
+    let mut close = false;
+    egui::Window::new("Game over")
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            let names = &board_render_data.names;
+            ui.heading(RichText::new(format!("{} wins!", names[winner])).color(colour(winner)));
+            ui.label(match goal {
+                Goal::Eliminate => "Last player standing".to_string(),
+                Goal::HoldTerritories(count) => format!("Held {} territories", count),
+                Goal::Survive(turns) => format!("Survived {} turns", turns),
+            });
+            ui.label(format!(
+                "Rounds: {}    Attacks: {}",
+                board.round + 1,
+                player_stats.iter().map(|stats| stats.attacks).sum::<u32>()
+            ));
+
+            egui::Grid::new("final stats").striped(true).show(ui, |ui| {
+                ui.label("");
+                ui.label("Territories");
+                ui.label("Dice");
+                ui.label("Most held");
+                ui.label("Attacks won");
+                ui.end_row();
+
+                let territories = board.count_territories();
+                for player in 0..board.stockpiles.len() {
+                    let dice: u32 = board
+                        .territories
+                        .iter()
+                        .filter(|territory| territory.owner == player)
+                        .map(|territory| territory.dice)
+                        .sum();
+                    let stats = player_stats.get(player).copied().unwrap_or_default();
+                    ui.label(RichText::new(names[player].as_str()).color(colour(player)));
+                    ui.label(territories[player].to_string());
+                    ui.label(dice.to_string());
+                    ui.label(stats.most_territories.to_string());
+                    ui.label(format!("{} / {}", stats.attacks_won, stats.attacks));
+                    ui.end_row();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                // the starting board keeps its seed so the dice roll the same way again, with the
+                // same players in the same seats
+                if ui.button("Rematch").clicked() {
+                    load_board_event.send(LoadBoardEvent {
+                        board: game_start.board.clone(),
+                        seats: Some(bots.seats.clone()),
+                        start: None,
+                    });
+                    close = true;
+                }
+                if ui
+                    .add_enabled(
+                        board_gen_settings.validate().is_ok(),
+                        egui::Button::new("New game"),
+                    )
+                    .clicked()
+                {
+                    regenerate_board_event.send(RegenerateBoardEvent);
+                    close = true;
+                }
+                if ui.button("Close").clicked() {
+                    close = true;
+                }
+            });
+        });
+    if close {
+        *stats = None;
+    }
+}
@@ -0,0 +1,344 @@
+use super::{AttackLog, GameStateEvent};
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use dicewars_core::{
+    board::{AttackOutcome, Board},
+    replay::{Replay, ReplayAction},
+};
+
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UndoHistory {
+            mode: UndoMode::Selection,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            turn_start: 0,
+            locked: Vec::new(),
+        })
+        .add_system(undo_keys);
+    }
+}
+
+/// what the current player is allowed to take back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoMode {
+    Off,
+    /// only selecting and deselecting territories
+    Selection,
+    /// attacks can be undone too. the board's rng is restored with it and the undone attacks have
+    /// to be made again before any other, so redoing an attack rolls the same dice instead of
+    /// giving a reroll
+    Casual,
+}
+
+struct Snapshot {
+    board: Board,
+    selection: Option<usize>,
+    /// replay actions recorded this turn
+    actions: Vec<ReplayAction>,
+}
+
+/// snapshots of the current player's turn, cleared whenever a turn ends
+pub struct UndoHistory {
+    pub mode: UndoMode,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// number of replay actions recorded before this turn
+    turn_start: usize,
+    /// every attack made this turn in order, including ones that have been undone
+    locked: Vec<(usize, usize)>,
+}
+
+impl UndoHistory {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// forget everything, called at the start of every turn with the number of replay actions
+    /// recorded so far
+    pub fn reset(&mut self, turn_start: usize) {
+        self.undo.clear();
+        self.redo.clear();
+        self.turn_start = turn_start;
+        self.locked.clear();
+    }
+
+    /// whether the current player may attack from `first` to `second`. once an attack has been
+    /// undone the same attacks have to be made again in order, other attacks would roll the
+    /// restored rng differently
+    pub fn allows_attack(&self, first: usize, second: usize, replay: &Replay) -> bool {
+        let made = turn_attacks(self.turn_actions(replay)).count();
+        self.locked
+            .get(made)
+            .map_or(true, |&locked| locked == (first, second))
+    }
+
+    /// remember an attack made this turn, call it before the attack is recorded in the replay
+    pub fn record_attack(&mut self, first: usize, second: usize, replay: &Replay) {
+        let made = turn_attacks(self.turn_actions(replay)).count();
+        if made == self.locked.len() {
+            self.locked.push((first, second));
+        }
+    }
+
+    /// remember the state before a change
+    pub fn record(&mut self, board: &Board, selection: Option<usize>, replay: &Replay) {
+        if self.mode == UndoMode::Off {
+            return;
+        }
+        let snapshot = self.snapshot(board, selection, replay);
+        self.undo.push(snapshot);
+        self.redo.clear();
+    }
+
+    pub fn undo(
+        &mut self,
+        board: &mut Board,
+        selection: &mut Option<usize>,
+        replay: &mut Replay,
+        attack_log: &mut AttackLog,
+    ) {
+        if let Some(snapshot) = self.undo.pop() {
+            let current = self.snapshot(board, *selection, replay);
+            self.redo.push(current);
+            self.restore(snapshot, board, selection, replay, attack_log);
+        }
+    }
+
+    pub fn redo(
+        &mut self,
+        board: &mut Board,
+        selection: &mut Option<usize>,
+        replay: &mut Replay,
+        attack_log: &mut AttackLog,
+    ) {
+        if let Some(snapshot) = self.redo.pop() {
+            let current = self.snapshot(board, *selection, replay);
+            self.undo.push(current);
+            self.restore(snapshot, board, selection, replay, attack_log);
+        }
+    }
+
+    /// replay actions recorded this turn, empty if the replay has been replaced since the last
+    /// reset
+    fn turn_actions<'a>(&self, replay: &'a Replay) -> &'a [ReplayAction] {
+        replay.actions.get(self.turn_start..).unwrap_or_default()
+    }
+
+    fn snapshot(&self, board: &Board, selection: Option<usize>, replay: &Replay) -> Snapshot {
+        Snapshot {
+            board: board.clone(),
+            selection,
+            actions: self.turn_actions(replay).to_vec(),
+        }
+    }
+
+    fn restore(
+        &self,
+        snapshot: Snapshot,
+        board: &mut Board,
+        selection: &mut Option<usize>,
+        replay: &mut Replay,
+        attack_log: &mut AttackLog,
+    ) {
+        // the log holds the same attacks as the replay, swap this turn's for the snapshot's
+        let made = turn_attacks(self.turn_actions(replay)).count();
+        let turn_start = attack_log.attacks.len().saturating_sub(made);
+        attack_log.attacks.truncate(turn_start);
+        attack_log
+            .attacks
+            .extend(turn_attacks(&snapshot.actions).cloned());
+
+        *board = snapshot.board;
+        *selection = snapshot.selection;
+        replay.actions.truncate(self.turn_start);
+        replay.actions.extend(snapshot.actions);
+    }
+}
+
+fn turn_attacks(actions: &[ReplayAction]) -> impl Iterator<Item = &AttackOutcome> {
+    actions.iter().filter_map(|action| match action {
+        ReplayAction::Attack(outcome) => Some(outcome),
+        ReplayAction::EndTurn => None,
+    })
+}
+
+/// ctrl+z to undo, ctrl+y or ctrl+shift+z to redo
+fn undo_keys(
+    keys: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut game_state_events: EventWriter<GameStateEvent>,
+) {
+    // let text boxes have their own undo
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if ctrl && keys.just_pressed(KeyCode::Z) {
+        if shift {
+            game_state_events.send(GameStateEvent::Redo);
+        } else {
+            game_state_events.send(GameStateEvent::Undo);
+        }
+    }
+    if ctrl && keys.just_pressed(KeyCode::Y) {
+        game_state_events.send(GameStateEvent::Redo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dicewars_core::board::BoardGenSettings;
+
+    fn history(mode: UndoMode) -> UndoHistory {
+        UndoHistory {
+            mode,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            turn_start: 0,
+            locked: Vec::new(),
+        }
+    }
+
+    /// a new game, its empty replay and attack log
+    fn game() -> (Board, Replay, AttackLog) {
+        let settings = BoardGenSettings {
+            seed: Some(5),
+            ..Default::default()
+        };
+        let board = Board::generate(&settings).unwrap();
+        let replay = Replay::new(&settings, &board);
+        let attack_log = AttackLog {
+            attacks: Vec::new(),
+        };
+        (board, replay, attack_log)
+    }
+
+    /// makes an attack the way the game does, recording it everywhere
+    fn attack(
+        history: &mut UndoHistory,
+        board: &mut Board,
+        replay: &mut Replay,
+        attack_log: &mut AttackLog,
+        (first, second): (usize, usize),
+    ) {
+        history.record(board, None, replay);
+        history.record_attack(first, second, replay);
+        let outcome = board.try_attack(first, second).unwrap();
+        replay.record_attack(&outcome);
+        attack_log.attacks.push(outcome);
+    }
+
+    #[test]
+    fn attacks_can_be_undone_and_redone() {
+        let (mut board, mut replay, mut attack_log) = game();
+        let mut selection = None;
+        let mut history = history(UndoMode::Casual);
+        let before = format!("{:?}", board);
+
+        let first_move = board.all_moves()[0];
+        attack(
+            &mut history,
+            &mut board,
+            &mut replay,
+            &mut attack_log,
+            first_move,
+        );
+        let after = format!("{:?}", board);
+
+        history.undo(&mut board, &mut selection, &mut replay, &mut attack_log);
+        assert_eq!(format!("{:?}", board), before);
+        assert!(replay.actions.is_empty());
+        assert!(attack_log.attacks.is_empty());
+        assert!(!history.can_undo() && history.can_redo());
+
+        history.redo(&mut board, &mut selection, &mut replay, &mut attack_log);
+        assert_eq!(format!("{:?}", board), after);
+        assert_eq!(replay.actions.len(), 1);
+        assert_eq!(attack_log.attacks.len(), 1);
+    }
+
+    #[test]
+    fn undone_attacks_have_to_be_made_again_first() {
+        let (mut board, mut replay, mut attack_log) = game();
+        let mut selection = None;
+        let mut history = history(UndoMode::Casual);
+        let moves = board.all_moves();
+        for (first, second) in moves.iter() {
+            assert!(history.allows_attack(*first, *second, &replay));
+        }
+
+        attack(
+            &mut history,
+            &mut board,
+            &mut replay,
+            &mut attack_log,
+            moves[0],
+        );
+        history.undo(&mut board, &mut selection, &mut replay, &mut attack_log);
+        for (first, second) in moves.iter() {
+            let allowed = history.allows_attack(*first, *second, &replay);
+            assert_eq!(allowed, (*first, *second) == moves[0]);
+        }
+
+        // a new turn starts with nothing locked in
+        history.reset(replay.actions.len());
+        assert!(history.allows_attack(moves[1].0, moves[1].1, &replay));
+    }
+
+    #[test]
+    fn earlier_turns_are_kept_when_undoing() {
+        let (mut board, mut replay, mut attack_log) = game();
+        let mut selection = None;
+        let mut history = history(UndoMode::Selection);
+        board.finish_turn();
+        replay.record_end_turn();
+        history.reset(replay.actions.len());
+
+        history.record(&board, selection, &replay);
+        selection = Some(2);
+        history.undo(&mut board, &mut selection, &mut replay, &mut attack_log);
+        assert_eq!(selection, None);
+        assert_eq!(replay.actions.len(), 1);
+
+        history.reset(replay.actions.len());
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn nothing_is_recorded_when_undo_is_off() {
+        let (board, replay, _) = game();
+        let mut history = history(UndoMode::Off);
+        history.record(&board, Some(1), &replay);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn a_replaced_replay_doesnt_panic() {
+        let (mut board, mut replay, mut attack_log) = game();
+        let mut selection = None;
+        let mut history = history(UndoMode::Casual);
+        for _ in 0..3 {
+            board.finish_turn();
+            replay.record_end_turn();
+        }
+        history.reset(replay.actions.len());
+        history.record(&board, selection, &replay);
+
+        // a new game has started before the history heard about it
+        let (_, new_replay, _) = game();
+        replay = new_replay;
+        let (first, second) = board.all_moves()[0];
+        assert!(history.allows_attack(first, second, &replay));
+        history.undo(&mut board, &mut selection, &mut replay, &mut attack_log);
+    }
+}